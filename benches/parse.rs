@@ -0,0 +1,54 @@
+//! Compares the borrowing `Gemtext::parse` with the owned `FromStr` parse on a large document.
+//! Run with `cargo bench`.
+use std::{hint::black_box, str::FromStr, time::Instant};
+
+use remi::Gemtext;
+
+const SECTIONS: usize = 2000;
+const ITERATIONS: u32 = 50;
+
+fn document() -> String {
+    let mut res = String::new();
+    for i in 0..SECTIONS {
+        res.push_str(&format!(
+            "## Section {i}\n\
+             Some text of the section, long enough to look like a paragraph of a real page {i}.\n\
+             => gemini://example.org/{i}.gmi Link number {i}\n\
+             * first item\n\
+             * second item\n\
+             > a quoted line\n\
+             ```alt text\n\
+             preformatted line {i}\n\
+             ```\n\n"
+        ));
+    }
+    res
+}
+
+fn bench(name: &str, f: impl Fn()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    println!("{name}: {:?} per parse", start.elapsed() / ITERATIONS);
+}
+
+fn main() {
+    // `cargo test` also runs benchmarks without a harness, only `cargo bench` passes `--bench`
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+    let document = document();
+    println!(
+        "{} lines, {} bytes",
+        document.lines().count(),
+        document.len()
+    );
+    bench("Gemtext::parse", || {
+        black_box(Gemtext::parse(black_box(&document)));
+    });
+    bench("Gemtext::from_str", || {
+        black_box(Gemtext::from_str(black_box(&document)).unwrap());
+    });
+}
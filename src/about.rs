@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+use remi::gemtext::{Gemtext, GemtextEntry};
+
+/// Internal page of an `about:<name>` URL, built by the browser without touching the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AboutPage {
+    Bookmarks,
+    History,
+    Settings,
+    Stats,
+    Subscriptions,
+    /// Lists the other pages
+    Index,
+}
+
+impl AboutPage {
+    /// Pages listed by the index
+    pub const LISTED: [Self; 5] = [
+        Self::Bookmarks,
+        Self::History,
+        Self::Settings,
+        Self::Stats,
+        Self::Subscriptions,
+    ];
+
+    /// Page named `name`, `None` if there is no such page
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bookmarks" => Some(Self::Bookmarks),
+            "history" => Some(Self::History),
+            "settings" => Some(Self::Settings),
+            "stats" => Some(Self::Stats),
+            "subscriptions" => Some(Self::Subscriptions),
+            "" | "about" => Some(Self::Index),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bookmarks => "bookmarks",
+            Self::History => "history",
+            Self::Settings => "settings",
+            Self::Stats => "stats",
+            Self::Subscriptions => "subscriptions",
+            Self::Index => "about",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Bookmarks => "Bookmarked pages, by folder",
+            Self::History => "Pages visited in the current tab",
+            Self::Settings => "Preferences of the browser",
+            Self::Stats => "Most visited hosts",
+            Self::Subscriptions => "Subscribed pages and their new entries",
+            Self::Index => "List of the internal pages",
+        }
+    }
+}
+
+/// Name of the internal page requested by `url`, if it's an `about:` URL
+pub fn page_name(url: &str) -> Option<&str> {
+    url.strip_prefix("about:")
+}
+
+/// Page listing the internal pages, `unknown` is the requested name when there is no such page
+pub fn index(unknown: Option<&str>) -> Gemtext {
+    let mut data = vec![GemtextEntry::MajorHeading("Internal pages".to_string())];
+    if let Some(name) = unknown {
+        data.push(GemtextEntry::Text(format!(
+            "'about:{name}' doesn't exist, these pages do:"
+        )));
+    }
+    data.extend(AboutPage::LISTED.iter().map(|page| GemtextEntry::Link {
+        url: format!("about:{}", page.name()),
+        label: format!("about:{} - {}", page.name(), page.description()),
+    }));
+    Gemtext { data }
+}
@@ -0,0 +1,90 @@
+#![allow(dead_code)]
+use std::error::Error;
+
+pub const USAGE: &str = "\
+Usage: remi [OPTIONS] [URL]...
+       remi --fetch <URL> [--insecure]
+
+Options:
+  --home <URL>       Home page for this session
+  --width <PIXELS>   Initial window width
+  --height <PIXELS>  Initial window height
+  --insecure         Accept any certificate for this session
+  --fetch <URL>      Print the response to URL without opening a window.
+                     The body goes to stdout and the status line to stderr.
+                     Exits with 0 for a success, the first digit of the status
+                     otherwise, or 7 if no response was received.
+  -h, --help         Print this message";
+
+/// Command line arguments, every flag field is `None` or `false` when the flag isn't passed
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Pages opened in new tabs at startup
+    pub urls: Vec<String>,
+    pub home: Option<String>,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// URL requested by `--fetch`, in which case no window is opened
+    pub fetch: Option<String>,
+    pub insecure: bool,
+    pub help: bool,
+}
+
+#[derive(Debug)]
+pub struct ArgsError {
+    pub msg: String,
+}
+
+impl ArgsError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl Error for ArgsError {}
+
+impl Args {
+    /// Parses `args`, without the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut res = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--home" => res.home = Some(value(&arg, args.next())?),
+                "--width" => res.width = Some(size(&arg, args.next())?),
+                "--height" => res.height = Some(size(&arg, args.next())?),
+                "--fetch" => res.fetch = Some(value(&arg, args.next())?),
+                "--insecure" => res.insecure = true,
+                "-h" | "--help" => res.help = true,
+                _ if arg.starts_with('-') => {
+                    return Err(ArgsError::new(&format!("unknown option '{arg}'")));
+                }
+                _ => res.urls.push(arg),
+            }
+        }
+        Ok(res)
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, ArgsError> {
+    value.ok_or_else(|| ArgsError::new(&format!("'{flag}' needs a value")))
+}
+
+/// Window dimension in pixels, which must be positive
+fn size(flag: &str, raw: Option<String>) -> Result<f32, ArgsError> {
+    let raw = value(flag, raw)?;
+    match raw.parse::<f32>() {
+        Ok(size) if size.is_finite() && size > 0. => Ok(size),
+        _ => Err(ArgsError::new(&format!(
+            "'{flag}' needs a positive number of pixels, got '{raw}'"
+        ))),
+    }
+}
@@ -0,0 +1,142 @@
+#![allow(dead_code)]
+use remi::gemtext::{Gemtext, GemtextEntry};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    pub folder: Option<String>,
+}
+
+impl Bookmark {
+    pub fn new(url: &str, title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            url: url.to_string(),
+            folder: None,
+        }
+    }
+
+    /// Text shown for the bookmark, the URL when it has no title
+    pub fn name(&self) -> &str {
+        if self.title.trim().is_empty() {
+            &self.url
+        } else {
+            &self.title
+        }
+    }
+
+    /// Parses a `[folder<TAB>]url[ title]` line.
+    /// URLs never contain spaces or tabs, so lines written before bookmarks had titles or folders are still valid.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (folder, line) = match line.split_once('\t') {
+            Some((folder, line)) => (Some(folder.trim()), line.trim()),
+            None => (None, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+        let mut res = match line.split_once(' ') {
+            Some((url, title)) => Self::new(url, title.trim()),
+            None => Self::new(line, ""),
+        };
+        res.folder = folder
+            .filter(|folder| !folder.is_empty())
+            .map(|folder| folder.to_string());
+        Some(res)
+    }
+
+    pub fn to_line(&self) -> String {
+        // Both have to fit on a single line, and tabs separate the folder
+        let single_line = |s: &str| s.replace(['\r', '\n', '\t'], " ").trim().to_string();
+        let mut res = String::new();
+        if let Some(folder) = self.folder.as_deref().map(single_line) {
+            if !folder.is_empty() {
+                res.push_str(&folder);
+                res.push('\t');
+            }
+        }
+        res.push_str(&self.url);
+        let title = single_line(&self.title);
+        if !title.is_empty() {
+            res.push(' ');
+            res.push_str(&title);
+        }
+        res
+    }
+}
+
+pub fn from_lines(lines: &str) -> Vec<Bookmark> {
+    lines.lines().filter_map(Bookmark::from_line).collect()
+}
+
+pub fn to_lines(bookmarks: &[Bookmark]) -> String {
+    bookmarks
+        .iter()
+        .map(Bookmark::to_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Names of the folders used by `bookmarks`, in order of first use
+pub fn folders(bookmarks: &[Bookmark]) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for folder in bookmarks.iter().filter_map(|b| b.folder.as_ref()) {
+        if !res.contains(folder) {
+            res.push(folder.clone());
+        }
+    }
+    res
+}
+
+/// Gemtext document listing `bookmarks` as links, with a `##` heading per folder
+pub fn to_gemtext(bookmarks: &[Bookmark]) -> Gemtext {
+    let mut data = vec![GemtextEntry::MajorHeading("Bookmarks".to_string())];
+    let folders = folders(bookmarks);
+    for folder in std::iter::once(None).chain(folders.iter().map(Some)) {
+        if let Some(folder) = folder {
+            data.push(GemtextEntry::MediumHeading(folder.clone()));
+        }
+        data.extend(
+            bookmarks
+                .iter()
+                .filter(|bookmark| bookmark.folder.as_ref() == folder)
+                .map(|bookmark| GemtextEntry::Link {
+                    url: bookmark.url.clone(),
+                    label: bookmark.title.clone(),
+                }),
+        );
+    }
+    Gemtext { data }
+}
+
+/// Bookmarks for every link of `gemtext`, links under a `##` heading are put in a folder named after it
+pub fn from_gemtext(gemtext: &Gemtext) -> Vec<Bookmark> {
+    let mut res = vec![];
+    let mut folder = None;
+    for entry in &gemtext.data {
+        match entry {
+            GemtextEntry::MajorHeading(_) => folder = None,
+            GemtextEntry::MediumHeading(h) => folder = Some(h.trim().to_string()),
+            GemtextEntry::Link { url, label } if !url.is_empty() => {
+                let mut bookmark = Bookmark::new(url, label.trim());
+                bookmark.folder = folder.clone();
+                res.push(bookmark);
+            }
+            _ => (),
+        }
+    }
+    res
+}
+
+/// Appends the bookmarks of `new` whose URL isn't bookmarked yet, returns how many were added
+pub fn merge(bookmarks: &mut Vec<Bookmark>, new: Vec<Bookmark>) -> usize {
+    let len = bookmarks.len();
+    for bookmark in new {
+        if !bookmarks.iter().any(|b| b.url == bookmark.url) {
+            bookmarks.push(bookmark);
+        }
+    }
+    bookmarks.len() - len
+}
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+use std::{
+    error::Error,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+type CacheResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Default limit of the total size of the cached responses, in bytes
+pub const DEFAULT_MAX_SIZE: u64 = 50 * 1024 * 1024;
+
+/// A response stored by `Cache::put`
+#[derive(Debug, Clone)]
+pub struct CachedPage {
+    /// Raw response, header included
+    pub raw: Vec<u8>,
+    pub time: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    url: String,
+    size: u64,
+    time: SystemTime,
+}
+
+/// Raw responses of visited pages keyed by URL, used when the network fails.
+/// Each response is stored in `dir` as a file named after a hash of its URL, holding the URL on its first line.
+/// The least recently used responses are evicted once they take more than `max_size` bytes.
+#[derive(Debug)]
+pub struct Cache {
+    pub dir: Option<PathBuf>,
+    pub max_size: u64,
+    /// Least recently used first, responses loaded from `dir` are ordered by the time they were received
+    entries: Vec<CacheEntry>,
+}
+
+impl Cache {
+    pub fn load(dir: Option<PathBuf>, max_size: u64) -> Self {
+        let mut entries = vec![];
+        if let Some(files) = dir.as_ref().and_then(|dir| std::fs::read_dir(dir).ok()) {
+            for file in files.flatten() {
+                let Ok(metadata) = file.metadata() else {
+                    continue;
+                };
+                let Some(url) = read_url(&file.path()) else {
+                    continue;
+                };
+                entries.push(CacheEntry {
+                    url,
+                    size: metadata.len(),
+                    time: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+        entries.sort_by_key(|entry| entry.time);
+        let mut res = Self {
+            dir,
+            max_size,
+            entries,
+        };
+        res.evict();
+        res
+    }
+
+    /// Response stored for `url`, which becomes the most recently used one
+    pub fn get(&mut self, url: &str) -> Option<CachedPage> {
+        let i = self.entries.iter().position(|entry| entry.url == url)?;
+        let entry = self.entries.remove(i);
+        let time = entry.time;
+        self.entries.push(entry);
+        let mut reader = BufReader::new(std::fs::File::open(self.path(url)?).ok()?);
+        let mut first_line = vec![];
+        reader.read_until(b'\n', &mut first_line).ok()?;
+        // Another URL with the same hash
+        if first_line.strip_suffix(b"\n") != Some(url.as_bytes()) {
+            return None;
+        }
+        let mut raw = vec![];
+        reader.read_to_end(&mut raw).ok()?;
+        Some(CachedPage { raw, time })
+    }
+
+    /// Stores `raw` as the response for `url`, replacing the previous one, then evicts old responses
+    pub fn put(&mut self, url: &str, raw: &[u8]) -> CacheResult<()> {
+        let Some(path) = self.path(url) else {
+            return Ok(());
+        };
+        if let Some(dir) = &self.dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut data = Vec::with_capacity(url.len() + 1 + raw.len());
+        data.extend_from_slice(url.as_bytes());
+        data.push(b'\n');
+        data.extend_from_slice(raw);
+        std::fs::write(path, &data)?;
+        // Files are named after the hash, an entry with the same hash was overwritten
+        let hash = hash(url);
+        self.entries.retain(|entry| self::hash(&entry.url) != hash);
+        self.entries.push(CacheEntry {
+            url: url.to_string(),
+            size: data.len() as u64,
+            time: SystemTime::now(),
+        });
+        self.evict();
+        Ok(())
+    }
+
+    /// Removes the least recently used responses until the cache fits in `max_size`
+    pub fn evict(&mut self) {
+        let mut size: u64 = self.entries.iter().map(|entry| entry.size).sum();
+        while size > self.max_size && !self.entries.is_empty() {
+            let entry = self.entries.remove(0);
+            size -= entry.size;
+            if let Some(path) = self.path(&entry.url) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    fn path(&self, url: &str) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{:016x}", hash(url))))
+    }
+}
+
+/// URL on the first line of a cache file
+fn read_url(path: &Path) -> Option<String> {
+    let mut reader = BufReader::new(std::fs::File::open(path).ok()?);
+    let mut url = String::new();
+    reader.read_line(&mut url).ok()?;
+    url.strip_suffix('\n').map(|url| url.to_string())
+}
+
+/// 64-bit FNV-1a, which unlike the standard library hasher is stable across releases
+fn hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let dir = std::env::temp_dir().join(format!("remi-cache-test-{}", std::process::id()));
+        let url = |name| format!("gemini://example.org/{name}");
+        let raw = b"20 text/gemini\r\n# Page\n";
+        // Room for two responses
+        let entry_size = url("a").len() as u64 + 1 + raw.len() as u64;
+        let mut cache = Cache::load(Some(dir.clone()), 2 * entry_size);
+        cache.put(&url("a"), raw).unwrap();
+        cache.put(&url("b"), raw).unwrap();
+        cache.put(&url("c"), raw).unwrap();
+        assert!(cache.get(&url("a")).is_none());
+        // `b` is used again, so `c` is the one evicted next
+        assert_eq!(cache.get(&url("b")).unwrap().raw, raw);
+        cache.put(&url("d"), raw).unwrap();
+        assert!(cache.get(&url("c")).is_none());
+        assert!(cache.get(&url("b")).is_some());
+        assert!(cache.get(&url("d")).is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
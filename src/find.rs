@@ -0,0 +1,137 @@
+#![allow(dead_code)]
+use std::ops::Range;
+
+use eframe::egui::{text::LayoutJob, Color32, FontId, RichText, TextFormat, WidgetText};
+
+const MATCH_BG_COLOR: Color32 = Color32::from_rgb(84, 76, 48);
+const CURRENT_MATCH_BG_COLOR: Color32 = Color32::from_rgb(150, 120, 50);
+
+/// State of the find-in-page bar
+#[derive(Debug, Default)]
+pub struct Find {
+    pub query: String,
+    pub case_sensitive: bool,
+    /// Index of the selected match
+    pub current: usize,
+    /// Number of matches in the page, counted while it was last rendered
+    pub count: usize,
+    /// Matches seen so far while rendering the page
+    pub seen: usize,
+    /// Whether the selected match should be scrolled to
+    pub scroll: bool,
+    /// Whether the search field should take the keyboard focus
+    pub focus: bool,
+}
+
+impl Find {
+    /// Byte ranges of the non-overlapping matches of the query in `text`
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        let mut res = vec![];
+        if self.query.is_empty() {
+            return res;
+        }
+        let mut next_start = 0;
+        for (start, _) in text.char_indices() {
+            if start < next_start {
+                continue;
+            }
+            if let Some(len) = self.match_len(&text[start..]) {
+                res.push(start..start + len);
+                next_start = start + len;
+            }
+        }
+        res
+    }
+
+    /// Length in bytes of the match at the start of `text`, if there is one
+    fn match_len(&self, text: &str) -> Option<usize> {
+        let mut chars = text.chars();
+        let mut len = 0;
+        for q in self.query.chars() {
+            let c = chars.next()?;
+            let equal = if self.case_sensitive {
+                c == q
+            } else {
+                c.to_lowercase().eq(q.to_lowercase())
+            };
+            if !equal {
+                return None;
+            }
+            len += c.len_utf8();
+        }
+        Some(len)
+    }
+
+    pub fn next(&mut self) {
+        if self.count > 0 {
+            self.current = (self.current + 1) % self.count;
+            self.scroll = true;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if self.count > 0 {
+            self.current = (self.current + self.count - 1) % self.count;
+            self.scroll = true;
+        }
+    }
+
+    /// Restarts the search from the first match, after the query or its options changed
+    pub fn restart(&mut self) {
+        self.current = 0;
+        self.scroll = true;
+    }
+}
+
+/// `text` with the matches of `find` highlighted.
+/// The returned flag is set when `text` holds the selected match and it should be scrolled to.
+pub fn highlight(
+    find: &mut Option<Find>,
+    text: &str,
+    font_id: FontId,
+    color: Color32,
+    background: Color32,
+) -> (WidgetText, bool) {
+    let plain = || {
+        RichText::new(text)
+            .font(font_id.clone())
+            .color(color)
+            .background_color(background)
+            .into()
+    };
+    let Some(find) = find else {
+        return (plain(), false);
+    };
+    let matches = find.matches(text);
+    if matches.is_empty() {
+        return (plain(), false);
+    }
+
+    let format = |background| TextFormat {
+        font_id: font_id.clone(),
+        color,
+        background,
+        ..Default::default()
+    };
+    let mut job = LayoutJob::default();
+    let mut has_current = false;
+    let mut end = 0;
+    for range in matches {
+        job.append(&text[end..range.start], 0., format(background));
+        let is_current = find.seen == find.current;
+        has_current |= is_current;
+        job.append(
+            &text[range.clone()],
+            0.,
+            format(if is_current {
+                CURRENT_MATCH_BG_COLOR
+            } else {
+                MATCH_BG_COLOR
+            }),
+        );
+        find.seen += 1;
+        end = range.end;
+    }
+    job.append(&text[end..], 0., format(background));
+    (job.into(), has_current && find.scroll)
+}
@@ -1,21 +1,24 @@
 #![allow(dead_code)]
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Gemtext {
     pub data: Vec<GemtextEntry>,
 }
 
 #[derive(Debug)]
 pub struct GemtextParseError {
+    /// 1-based line number of `line` in the parsed document
+    pub line_number: usize,
     pub line: String,
     pub msg: String,
 }
 
 #[allow(dead_code)]
 impl GemtextParseError {
-    pub fn new(line: &str, msg: &str) -> Self {
+    pub fn new(line_number: usize, line: &str, msg: &str) -> Self {
         Self {
+            line_number,
             line: line.to_string(),
             msg: msg.to_string(),
         }
@@ -24,52 +27,85 @@ impl GemtextParseError {
 
 impl std::fmt::Display for GemtextParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "'{}' in line:\n    {}", self.msg, self.line)
+        write!(
+            f,
+            "line {}: '{}' in line:\n    {}",
+            self.line_number, self.msg, self.line
+        )
     }
 }
 
 impl std::error::Error for GemtextParseError {}
 
+impl std::fmt::Display for Gemtext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, entry) in self.data.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
 impl FromStr for Gemtext {
     type Err = GemtextParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut res: Vec<GemtextEntry> = vec![];
+        Ok(Gemtext::parse(s).into())
+    }
+}
+
+impl Gemtext {
+    /// Parses `s` without allocating a `String` per entry, every entry in the
+    /// returned `GemtextRef` borrows from `s`.
+    pub fn parse(s: &str) -> GemtextRef<'_> {
+        let mut res: Vec<GemtextEntryRef> = vec![];
         let mut preformatted_mode = false;
-        let mut preformatted_buffer = String::new();
-        let mut preformatted_alt_text = String::new();
+        let mut preformatted_body: Option<(usize, usize)> = None;
+        let mut preformatted_alt_text = "";
         for l in s.lines() {
             let l1 = l.trim_start();
+            // Only fences at the very start of a line toggle preformatting,
+            // indented backticks are part of the block or of the text
             if preformatted_mode {
-                if l1.starts_with("```") {
+                if l.starts_with("```") {
                     preformatted_mode = false;
-                    res.push(GemtextEntry::Preformatted {
-                        alt_text: preformatted_alt_text.clone(),
-                        body: preformatted_buffer.clone(),
+                    res.push(GemtextEntryRef::Preformatted {
+                        alt_text: preformatted_alt_text,
+                        body: if let Some((start, end)) = preformatted_body {
+                            &s[start..end]
+                        } else {
+                            ""
+                        },
                     });
-                    preformatted_alt_text.clear();
-                    preformatted_buffer.clear();
+                    preformatted_alt_text = "";
+                    preformatted_body = None;
                 } else {
-                    if preformatted_buffer.len() > 0 {
-                        preformatted_buffer.push('\n');
-                    }
-                    preformatted_buffer.push_str(l);
+                    // `l` is a subslice of `s`, so its offset can be recovered from the pointers
+                    let start = l.as_ptr() as usize - s.as_ptr() as usize;
+                    let end = start + l.len();
+                    preformatted_body = Some(match preformatted_body {
+                        Some((body_start, _)) => (body_start, end),
+                        None => (start, end),
+                    });
                 }
             } else {
                 if l1.starts_with("=>") {
                     let mut byte_counter = 0;
                     let mut word_start = 0;
                     let mut word_counter = 0;
-                    let mut url = String::new();
-                    let mut label = String::new();
+                    let mut url = "";
+                    let mut label = "";
                     for c in l1.chars() {
                         if c.is_whitespace() {
                             if word_counter == 1 {
                                 // url
-                                url = l1[word_start..byte_counter].to_string();
+                                url = &l1[word_start..byte_counter];
                             } else if word_counter > 1 {
                                 // rest is label
-                                label = l1[word_start..].to_string();
+                                label = &l1[word_start..];
                                 break;
                             }
                             word_counter += 1;
@@ -80,74 +116,560 @@ impl FromStr for Gemtext {
                         }
                     }
                     if url.is_empty() && word_counter == 1 && l1.len() > word_start {
-                        url = l1[word_start..].to_string();
+                        url = &l1[word_start..];
                     }
-                    res.push(GemtextEntry::Link { url, label });
-                } else if l1.starts_with("### ") {
-                    res.push(GemtextEntry::MinorHeading(if l1.len() > 4 {
-                        l1[4..].to_string()
-                    } else {
-                        String::new()
-                    }));
-                } else if l1.starts_with("## ") {
-                    res.push(GemtextEntry::MediumHeading(if l1.len() > 3 {
-                        l1[3..].to_string()
-                    } else {
-                        String::new()
-                    }));
-                } else if l1.starts_with("# ") {
-                    res.push(GemtextEntry::MajorHeading(if l1.len() > 2 {
-                        l1[2..].to_string()
-                    } else {
-                        String::new()
-                    }));
-                } else if l1.starts_with("* ") {
-                    let new_entry = if l1.len() > 2 {
-                        l1[2..].to_string()
+                    res.push(GemtextEntryRef::Link { url, label });
+                } else if let Some(h) = l1.strip_prefix("### ") {
+                    res.push(GemtextEntryRef::MinorHeading(h));
+                } else if let Some(h) = l1.strip_prefix("## ") {
+                    res.push(GemtextEntryRef::MediumHeading(h));
+                } else if let Some(h) = l1.strip_prefix("# ") {
+                    res.push(GemtextEntryRef::MajorHeading(h));
+                } else if let Some(new_entry) = l1.strip_prefix("* ") {
+                    // The space is required, see `GemtextEntry::List`
+                    if let Some(GemtextEntryRef::List(vec)) = res.last_mut() {
+                        vec.push(new_entry);
                     } else {
-                        String::new()
-                    };
-                    let mut new_list = true;
-                    if let Some(e) = res.last_mut() {
-                        match e {
-                            GemtextEntry::List(vec) => {
-                                new_list = false;
-                                vec.push(new_entry.clone());
-                            }
-                            _ => (),
-                        }
+                        res.push(GemtextEntryRef::List(vec![new_entry]));
                     }
-                    if new_list {
-                        res.push(GemtextEntry::List(vec![new_entry]));
-                    }
-                } else if l1.starts_with(">") {
-                    res.push(GemtextEntry::Quote(if l1.len() > 1 {
-                        l1[1..].to_string()
+                } else if let Some(q) = l1.strip_prefix('>') {
+                    // Like the other markers, a single space after `>` isn't part of the quote
+                    let q = q.strip_prefix(' ').unwrap_or(q);
+                    if let Some(GemtextEntryRef::Quote(lines)) = res.last_mut() {
+                        lines.push(q);
                     } else {
-                        String::new()
-                    }));
-                } else if l1.starts_with("```") {
-                    preformatted_mode = true;
-                    if l1.len() > 3 {
-                        preformatted_alt_text.push_str(&s[3..]);
+                        res.push(GemtextEntryRef::Quote(vec![q]));
                     }
+                } else if let Some(alt_text) = l.strip_prefix("```") {
+                    preformatted_mode = true;
+                    preformatted_alt_text = alt_text;
                 } else {
-                    res.push(GemtextEntry::Text(l.to_string()));
+                    // Text lines are shown as they are, leading whitespace included
+                    res.push(GemtextEntryRef::Text(l));
                 }
             }
         }
-        Ok(Gemtext { data: res })
+        GemtextRef { data: res }
+    }
+
+    /// `(url, label)` of every link, in document order
+    ///
+    /// ```
+    /// use remi::Gemtext;
+    ///
+    /// let gemtext: Gemtext = "# Title\n=> gemini://example.org/ An example".parse().unwrap();
+    /// let links: Vec<_> = gemtext.links().collect();
+    /// assert_eq!(links, [("gemini://example.org/", "An example")]);
+    /// ```
+    pub fn links(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.data.iter().filter_map(|entry| match entry {
+            GemtextEntry::Link { url, label } => Some((url.as_str(), label.as_str())),
+            _ => None,
+        })
+    }
+
+    /// `(level, text)` of every heading in document order, see `GemtextEntry::heading`
+    pub fn headings(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.data.iter().filter_map(GemtextEntry::heading)
     }
 }
 
-#[derive(Debug)]
+/// Borrowed counterpart of `Gemtext`, produced by `Gemtext::parse`.
+#[derive(Debug, PartialEq)]
+pub struct GemtextRef<'a> {
+    pub data: Vec<GemtextEntryRef<'a>>,
+}
+
+/// Borrowed counterpart of `GemtextEntry`.
+/// `Preformatted::body` is the verbatim source slice, so it keeps the original line endings.
+#[derive(Debug, PartialEq)]
+pub enum GemtextEntryRef<'a> {
+    Text(&'a str),
+    Link { url: &'a str, label: &'a str },
+    MinorHeading(&'a str),
+    MediumHeading(&'a str),
+    MajorHeading(&'a str),
+    List(Vec<&'a str>),
+    Quote(Vec<&'a str>),
+    Preformatted { alt_text: &'a str, body: &'a str },
+}
+
+impl From<GemtextRef<'_>> for Gemtext {
+    fn from(value: GemtextRef<'_>) -> Self {
+        Gemtext {
+            data: value.data.into_iter().map(GemtextEntry::from).collect(),
+        }
+    }
+}
+
+impl From<GemtextEntryRef<'_>> for GemtextEntry {
+    fn from(value: GemtextEntryRef<'_>) -> Self {
+        match value {
+            GemtextEntryRef::Text(t) => GemtextEntry::Text(t.to_string()),
+            GemtextEntryRef::Link { url, label } => GemtextEntry::Link {
+                url: url.to_string(),
+                label: label.to_string(),
+            },
+            GemtextEntryRef::MinorHeading(h) => GemtextEntry::MinorHeading(h.to_string()),
+            GemtextEntryRef::MediumHeading(h) => GemtextEntry::MediumHeading(h.to_string()),
+            GemtextEntryRef::MajorHeading(h) => GemtextEntry::MajorHeading(h.to_string()),
+            GemtextEntryRef::List(elems) => {
+                GemtextEntry::List(elems.into_iter().map(|el| el.to_string()).collect())
+            }
+            GemtextEntryRef::Quote(lines) => {
+                GemtextEntry::Quote(lines.into_iter().map(|l| l.to_string()).collect())
+            }
+            GemtextEntryRef::Preformatted { alt_text, body } => GemtextEntry::Preformatted {
+                alt_text: alt_text.to_string(),
+                body: body.lines().collect::<Vec<_>>().join("\n"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum GemtextEntry {
     Text(String),
-    Link { url: String, label: String },
+    Link {
+        url: String,
+        label: String,
+    },
     MinorHeading(String),
     MediumHeading(String),
     MajorHeading(String),
+    /// Items of consecutive list lines. Only lines starting with `* `, an asterisk then a space,
+    /// are list items, indentation aside. The item is everything after that space, so `* ` is an
+    /// empty item and `*  two spaces` keeps the second space. `*` alone and `*x` are text lines.
+    ///
+    /// ```
+    /// use remi::{gemtext::GemtextEntry, Gemtext};
+    ///
+    /// let gemtext: Gemtext = "* \n*  two spaces\n*\n*x".parse().unwrap();
+    /// assert_eq!(
+    ///     gemtext.data,
+    ///     [
+    ///         GemtextEntry::List(vec!["".to_string(), " two spaces".to_string()]),
+    ///         GemtextEntry::Text("*".to_string()),
+    ///         GemtextEntry::Text("*x".to_string()),
+    ///     ]
+    /// );
+    /// ```
     List(Vec<String>),
-    Quote(String),
-    Preformatted { alt_text: String, body: String },
+    /// Lines of consecutive quote lines, without their `>` marker and the space after it
+    ///
+    /// ```
+    /// use remi::{gemtext::GemtextEntry, Gemtext};
+    ///
+    /// let gemtext: Gemtext = "> To be,\n>\n>or not\ntext\n> again".parse().unwrap();
+    /// assert_eq!(
+    ///     gemtext.data,
+    ///     [
+    ///         GemtextEntry::Quote(vec!["To be,".to_string(), "".to_string(), "or not".to_string()]),
+    ///         GemtextEntry::Text("text".to_string()),
+    ///         GemtextEntry::Quote(vec!["again".to_string()]),
+    ///     ]
+    /// );
+    /// ```
+    Quote(Vec<String>),
+    Preformatted {
+        alt_text: String,
+        body: String,
+    },
+}
+
+impl GemtextEntry {
+    /// Level (1 for `#` to 3 for `###`) and text of a heading
+    pub fn heading(&self) -> Option<(usize, &str)> {
+        match self {
+            GemtextEntry::MajorHeading(h) => Some((1, h)),
+            GemtextEntry::MediumHeading(h) => Some((2, h)),
+            GemtextEntry::MinorHeading(h) => Some((3, h)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GemtextEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GemtextEntry::Text(t) => write!(f, "{t}"),
+            GemtextEntry::Link { url, label } => {
+                if label.is_empty() {
+                    write!(f, "=> {url}")
+                } else {
+                    write!(f, "=> {url} {label}")
+                }
+            }
+            GemtextEntry::MinorHeading(h) => write!(f, "### {h}"),
+            GemtextEntry::MediumHeading(h) => write!(f, "## {h}"),
+            GemtextEntry::MajorHeading(h) => write!(f, "# {h}"),
+            GemtextEntry::List(elems) => {
+                for (i, el) in elems.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "* {el}")?;
+                }
+                Ok(())
+            }
+            GemtextEntry::Quote(lines) => {
+                for (i, l) in lines.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    if l.is_empty() {
+                        write!(f, ">")?;
+                    } else {
+                        write!(f, "> {l}")?;
+                    }
+                }
+                Ok(())
+            }
+            GemtextEntry::Preformatted { alt_text, body } => {
+                writeln!(f, "```{alt_text}")?;
+                if !body.is_empty() {
+                    writeln!(f, "{body}")?;
+                }
+                write!(f, "```")
+            }
+        }
+    }
+}
+
+/// Anchor name of a heading, used to link to it with a URL fragment.
+/// The text is lowercased, runs of whitespace become a single `-`,
+/// and anything but letters, digits, `-` and `_` is dropped.
+pub fn slug(heading: &str) -> String {
+    let mut res = String::new();
+    for word in heading.split_whitespace() {
+        let word: String = word
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect();
+        if word.is_empty() {
+            continue;
+        }
+        if !res.is_empty() {
+            res.push('-');
+        }
+        res.push_str(&word);
+    }
+    res
+}
+
+/// Part of a text line split by `linkify`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span<'a> {
+    Text(&'a str),
+    Url(&'a str),
+}
+
+/// Schemes of the URLs found by `linkify`, others are left as text to avoid false positives
+const LINKIFIED_SCHEMES: [&str; 3] = ["gemini://", "http://", "https://"];
+
+/// Splits `text` around the bare URLs it contains, `None` if there aren't any.
+/// A URL has to start a word and ends at whitespace, trailing punctuation isn't part of it.
+pub fn linkify(text: &str) -> Option<Vec<Span<'_>>> {
+    let mut res = vec![];
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let starts_word = text[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let scheme = LINKIFIED_SCHEMES
+            .iter()
+            .find(|scheme| text[i..].starts_with(*scheme));
+        let Some(scheme) = scheme.filter(|_| starts_word) else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        let end = text[i..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+            .map_or(text.len(), |len| i + len);
+        let url = trim_url_end(&text[i..end]);
+        if url.len() == scheme.len() {
+            i += scheme.len();
+            continue;
+        }
+        if text_start < i {
+            res.push(Span::Text(&text[text_start..i]));
+        }
+        res.push(Span::Url(url));
+        i += url.len();
+        text_start = i;
+    }
+    if res.is_empty() {
+        return None;
+    }
+    if text_start < text.len() {
+        res.push(Span::Text(&text[text_start..]));
+    }
+    Some(res)
+}
+
+/// Drops the punctuation ending a sentence or closing a parenthesis opened before the URL
+fn trim_url_end(mut url: &str) -> &str {
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        url = match trimmed.strip_suffix(')') {
+            Some(rest) if trimmed.matches('(').count() < trimmed.matches(')').count() => rest,
+            _ => return trimmed,
+        };
+    }
+}
+
+/// Markdown version of `gemtext`, every entry becomes its own block.
+/// Preformatted blocks become fenced code blocks with the alt text as their info string,
+/// the text of the other entries is escaped so that it isn't read as formatting.
+pub fn to_markdown(gemtext: &Gemtext) -> String {
+    let mut blocks = vec![];
+    for entry in &gemtext.data {
+        blocks.push(match entry {
+            // Blocks are already separated by blank lines
+            GemtextEntry::Text(t) if t.trim().is_empty() => continue,
+            // Indented lines would become code blocks
+            GemtextEntry::Text(t) => escape_markdown(t.trim_start()),
+            // Only absolute URLs can be autolinks
+            GemtextEntry::Link { url, label }
+                if label.trim().is_empty()
+                    && crate::url::scheme(url).is_some()
+                    && !url.contains(['<', '>']) =>
+            {
+                format!("<{url}>")
+            }
+            GemtextEntry::Link { url, label } => {
+                let label = if label.trim().is_empty() {
+                    url
+                } else {
+                    label.trim()
+                };
+                // Parentheses in the destination would end it early
+                let url = if url.contains(['(', ')', '<', '>']) {
+                    format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+                } else {
+                    url.clone()
+                };
+                format!("[{}]({url})", escape_markdown(label))
+            }
+            GemtextEntry::MinorHeading(h) => format!("### {}", escape_markdown(h)),
+            GemtextEntry::MediumHeading(h) => format!("## {}", escape_markdown(h)),
+            GemtextEntry::MajorHeading(h) => format!("# {}", escape_markdown(h)),
+            GemtextEntry::List(elems) => elems
+                .iter()
+                .map(|el| format!("- {}", escape_markdown(el)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            // Each line stays its own paragraph of the blockquote
+            GemtextEntry::Quote(lines) => lines
+                .iter()
+                .map(|l| format!("> {}", escape_markdown(l)).trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n>\n"),
+            GemtextEntry::Preformatted { alt_text, body } => {
+                // The fence has to be longer than any run of backticks starting a line of the body
+                let longest = body
+                    .lines()
+                    .map(|l| l.trim_start().chars().take_while(|&c| c == '`').count())
+                    .max()
+                    .unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                let mut block = format!("{fence}{}\n", alt_text.trim());
+                if !body.is_empty() {
+                    block.push_str(body);
+                    block.push('\n');
+                }
+                block.push_str(&fence);
+                block
+            }
+        });
+    }
+    blocks.join("\n\n")
+}
+
+/// `text` with a backslash before the characters Markdown would read as formatting.
+/// Markers that only start a block, like `#` or `1.`, are escaped at the start of `text`.
+fn escape_markdown(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    for (i, c) in text.char_indices() {
+        let starts_block = match i {
+            0 => matches!(c, '#' | '>' | '-' | '+' | '='),
+            // Ordered list items, like `1.` or `2)`
+            _ => i == digits && matches!(c, '.' | ')'),
+        };
+        if starts_block
+            || matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '&' | '~' | '|'
+            )
+        {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Vec<GemtextEntry> {
+        s.parse::<Gemtext>().unwrap().data
+    }
+
+    #[test]
+    fn preformatted_alt_text() {
+        assert_eq!(
+            parse("text\n```rust\nfn main() {}\n```\nmore text"),
+            [
+                GemtextEntry::Text("text".to_string()),
+                GemtextEntry::Preformatted {
+                    alt_text: "rust".to_string(),
+                    body: "fn main() {}".to_string(),
+                },
+                GemtextEntry::Text("more text".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn preformatted_without_alt_text() {
+        assert_eq!(
+            parse("```\nfn main() {}\n```"),
+            [GemtextEntry::Preformatted {
+                alt_text: String::new(),
+                body: "fn main() {}".to_string(),
+            }]
+        );
+    }
+
+    /// Written the way `Display` writes it, so it also survives the round trip unchanged
+    const DOCUMENT: &str = "# Title
+Some text
+  indented text
+
+=> gemini://example.org/ A link
+=> gemini://example.org/no-label
+## Section
+### Subsection
+* one
+* two
+> To be,
+>
+> or not
+```alt text
+  preformatted
+* not a list
+```
+```
+```";
+
+    #[test]
+    fn display_round_trip() {
+        let gemtext: Gemtext = DOCUMENT.parse().unwrap();
+        let displayed = gemtext.to_string();
+        assert_eq!(displayed, DOCUMENT);
+        assert_eq!(displayed.parse::<Gemtext>().unwrap(), gemtext);
+    }
+
+    #[test]
+    fn slug_rules() {
+        assert_eq!(slug("Getting Started"), "getting-started");
+        assert_eq!(slug("  What's   new?  "), "whats-new");
+        assert_eq!(slug("Part 2: the_end"), "part-2-the_end");
+        assert_eq!(slug("Écrire en Français"), "écrire-en-français");
+        assert_eq!(slug("a - b"), "a---b");
+        assert_eq!(slug("!!! ???"), "");
+    }
+
+    #[test]
+    fn quote_marker_space() {
+        assert_eq!(parse("> x"), parse(">x"));
+        assert_eq!(parse(">x"), [GemtextEntry::Quote(vec!["x".to_string()])]);
+        // Only the first space belongs to the marker
+        assert_eq!(
+            parse(">   x"),
+            [GemtextEntry::Quote(vec!["  x".to_string()])]
+        );
+    }
+
+    #[test]
+    fn indented_fence_stays_in_block() {
+        assert_eq!(
+            parse("```md\nExample:\n  ```\n  code\n  ```\n```\n  ```not a fence"),
+            [
+                GemtextEntry::Preformatted {
+                    alt_text: "md".to_string(),
+                    body: "Example:\n  ```\n  code\n  ```".to_string(),
+                },
+                GemtextEntry::Text("  ```not a fence".to_string()),
+            ]
+        );
+    }
+
+    fn markdown(s: &str) -> String {
+        to_markdown(&s.parse().unwrap())
+    }
+
+    #[test]
+    fn markdown_escapes_formatting() {
+        assert_eq!(
+            markdown("# C# *is* [fun]\n1. not a list\n  - indented\nx < y & _z_ `c`"),
+            "# C# \\*is\\* \\[fun\\]\n\n1\\. not a list\n\n\\- indented\n\nx \\< y \\& \\_z\\_ \\`c\\`"
+        );
+        assert_eq!(
+            markdown("#hashtag\n> > nested"),
+            "\\#hashtag\n\n> \\> nested"
+        );
+    }
+
+    #[test]
+    fn markdown_fence_outgrows_backticks() {
+        assert_eq!(
+            markdown("```rust\nlet s = \"```\";\n  ````\n```"),
+            "`````rust\nlet s = \"```\";\n  ````\n`````"
+        );
+        assert_eq!(markdown("```\ncode\n```"), "```\ncode\n```");
+    }
+
+    #[test]
+    fn markdown_lists_links_and_quotes() {
+        assert_eq!(markdown("* one\n* *two*"), "- one\n- \\*two\\*");
+        assert_eq!(
+            markdown(
+                "=> gemini://h/ A [label]\n=> gemini://h/\n=> page.gmi\n=> gemini://h/a_(b) Odd URL"
+            ),
+            "[A \\[label\\]](gemini://h/)\n\n<gemini://h/>\n\n[page.gmi](page.gmi)\n\n[Odd URL](<gemini://h/a_(b)>)"
+        );
+        assert_eq!(
+            markdown("> To *be*,\n>\n> or not"),
+            "> To \\*be\\*,\n>\n>\n>\n> or not"
+        );
+    }
+
+    #[test]
+    fn links_and_headings() {
+        let gemtext: Gemtext = DOCUMENT.parse().unwrap();
+        assert_eq!(
+            gemtext.links().collect::<Vec<_>>(),
+            [
+                ("gemini://example.org/", "A link"),
+                ("gemini://example.org/no-label", "")
+            ]
+        );
+        assert_eq!(
+            gemtext.headings().collect::<Vec<_>>(),
+            [(1, "Title"), (2, "Section"), (3, "Subsection")]
+        );
+        // Lines of preformatted blocks are neither
+        let gemtext: Gemtext = "```\n# Title\n=> gemini://example.org/\n```"
+            .parse()
+            .unwrap();
+        assert_eq!(gemtext.links().count(), 0);
+        assert_eq!(gemtext.headings().count(), 0);
+    }
 }
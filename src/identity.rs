@@ -0,0 +1,145 @@
+#![allow(dead_code)]
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+
+use crate::url::{percent_decode, percent_encode};
+
+type IdentityResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// How long a client identity is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CertScope {
+    /// Stored with the other identities, it's presented again in the next sessions
+    #[default]
+    Persistent,
+    /// Only kept in memory, it's gone once the browser exits
+    Transient,
+}
+
+/// A client certificate and its private key, both PEM encoded
+#[derive(Debug, Clone)]
+pub struct ClientIdentity {
+    pub cert_pem: String,
+    pub key_pem: String,
+    pub scope: CertScope,
+}
+
+impl ClientIdentity {
+    pub fn from_files(cert_path: &Path, key_path: &Path, scope: CertScope) -> IdentityResult<Self> {
+        let res = Self {
+            cert_pem: std::fs::read_to_string(cert_path)?,
+            key_pem: std::fs::read_to_string(key_path)?,
+            scope,
+        };
+        // Make sure the files are usable before accepting them
+        res.parse()?;
+        Ok(res)
+    }
+
+    /// Generates a new self-signed certificate for `host`
+    pub fn generate(host: &str, scope: CertScope) -> IdentityResult<Self> {
+        let host = host.split(':').next().unwrap_or_default();
+        let certified = rcgen::generate_simple_self_signed(vec![host.to_string()])?;
+        Ok(Self {
+            cert_pem: certified.cert.pem(),
+            key_pem: certified.key_pair.serialize_pem(),
+            scope,
+        })
+    }
+
+    pub fn parse(&self) -> IdentityResult<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+        let certs = CertificateDer::pem_slice_iter(self.cert_pem.as_bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err("no certificate found in PEM data".into());
+        }
+        let key = PrivateKeyDer::from_pem_slice(self.key_pem.as_bytes())?;
+        Ok((certs, key))
+    }
+}
+
+/// Client identities keyed by the `host[:port]` they are presented to.
+/// Each persistent identity is stored in `dir` as a single `<host>.pem` file holding the certificate and the key.
+/// Transient identities are never written to disk.
+#[derive(Debug, Default)]
+pub struct Identities {
+    pub hosts: HashMap<String, ClientIdentity>,
+    pub dir: Option<PathBuf>,
+}
+
+impl Identities {
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let mut hosts = HashMap::new();
+        if let Some(entries) = dir.as_ref().and_then(|dir| std::fs::read_dir(dir).ok()) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Some(host) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".pem"))
+                else {
+                    continue;
+                };
+                if let Ok(pem) = std::fs::read_to_string(&path) {
+                    hosts.insert(
+                        percent_decode(host),
+                        ClientIdentity {
+                            cert_pem: pem.clone(),
+                            key_pem: pem,
+                            scope: CertScope::Persistent,
+                        },
+                    );
+                }
+            }
+        }
+        Self { hosts, dir }
+    }
+
+    pub fn get(&self, host: &str) -> Option<&ClientIdentity> {
+        self.hosts.get(host)
+    }
+
+    /// Sets the identity presented to `host`.
+    /// A transient identity also removes the file of the persistent one it replaces.
+    pub fn insert(&mut self, host: &str, identity: ClientIdentity) -> IdentityResult<()> {
+        if let Some(dir) = &self.dir {
+            match identity.scope {
+                CertScope::Persistent => {
+                    std::fs::create_dir_all(dir)?;
+                    let mut pem = identity.cert_pem.clone();
+                    if !pem.ends_with('\n') {
+                        pem.push('\n');
+                    }
+                    pem.push_str(&identity.key_pem);
+                    std::fs::write(self.path(dir, host), pem)?;
+                }
+                CertScope::Transient => {
+                    let replaced = self.hosts.get(host).map(|identity| identity.scope);
+                    if replaced == Some(CertScope::Persistent) {
+                        std::fs::remove_file(self.path(dir, host))?;
+                    }
+                }
+            }
+        }
+        self.hosts.insert(host.to_string(), identity);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, host: &str) -> IdentityResult<()> {
+        if let Some(identity) = self.hosts.remove(host) {
+            if let (Some(dir), CertScope::Persistent) = (&self.dir, identity.scope) {
+                std::fs::remove_file(self.path(dir, host))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, dir: &Path, host: &str) -> PathBuf {
+        dir.join(format!("{}.pem", percent_encode(host)))
+    }
+}
@@ -0,0 +1,28 @@
+//! Core of the remi Gemini browser: URLs, requests, and the parsing of responses and Gemtext.
+//! The browser is a user interface on top of it, headless tools can use it the same way.
+//!
+//! ```no_run
+//! use remi::{fetch, response::SuccessBody, GeminiResponse, Gemtext, RequestOptions};
+//!
+//! let (response, _raw) = fetch("gemini://geminiprotocol.net/", RequestOptions::default())?;
+//! if let GeminiResponse::Success { body: SuccessBody::Text(text), .. } = response {
+//!     let gemtext: Gemtext = text.parse()?;
+//!     for (url, label) in gemtext.links() {
+//!         println!("{url} {label}");
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+pub mod gemtext;
+pub mod identity;
+pub mod protocol;
+pub mod request;
+pub mod response;
+pub mod url;
+pub mod verifier;
+
+pub use gemtext::Gemtext;
+pub use request::{
+    fetch, request, request_raw, CancelHandle, RequestError, RequestOptions, RequestResult,
+};
+pub use response::GeminiResponse;
@@ -0,0 +1,212 @@
+#![allow(dead_code)]
+use std::{
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use remi::{
+    gemtext::{Gemtext, GemtextEntry},
+    identity::{ClientIdentity, Identities},
+    request::{forget_mismatch, request_header, CancelHandle, RequestOptions},
+    response::ResponseHeader,
+    url::{self, GeminiUrl},
+    verifier::KnownHosts,
+};
+
+/// Number of links checked at the same time
+const WORKERS: usize = 4;
+/// Connect and read timeout of every check, shorter than the one of regular requests
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Link to check, with what is needed to send its request
+struct Job {
+    index: usize,
+    server_name: String,
+    url: String,
+    identity: Option<ClientIdentity>,
+}
+
+/// Status of the links of a page, requested on a small pool of worker threads.
+/// Only the header of every response is read.
+pub struct LinkCheck {
+    pub page_url: String,
+    pub links: Vec<String>,
+    /// Header of the response to each link, or why there isn't one. `None` until it's checked.
+    pub results: Vec<Option<Result<ResponseHeader, String>>>,
+    /// Number of links that weren't checked because they don't use the gemini scheme
+    pub skipped: usize,
+    receiver: Receiver<(usize, Result<ResponseHeader, String>)>,
+    cancel: Vec<CancelHandle>,
+}
+
+impl LinkCheck {
+    /// Starts checking `links`, absolute URLs of links found on `page_url`.
+    /// Duplicates are checked once.
+    pub fn start(
+        page_url: &str,
+        links: impl IntoIterator<Item = String>,
+        known_hosts: &Arc<Mutex<KnownHosts>>,
+        identities: &Identities,
+        options: RequestOptions,
+    ) -> Self {
+        let mut checked: Vec<String> = vec![];
+        let mut jobs = vec![];
+        let mut skipped = 0;
+        for link in links {
+            if checked.contains(&link) {
+                continue;
+            }
+            match GeminiUrl::parse(&link) {
+                Ok(parsed) if parsed.scheme == "gemini" => {
+                    let server_name = parsed.authority();
+                    jobs.push(Job {
+                        index: checked.len(),
+                        identity: identities.get(&server_name).cloned(),
+                        server_name,
+                        url: link.clone(),
+                    });
+                    checked.push(link);
+                }
+                _ if url::scheme(&link).is_some_and(|scheme| scheme != "gemini") => skipped += 1,
+                _ => {
+                    // Checked right away, it can't be requested
+                    jobs.push(Job {
+                        index: checked.len(),
+                        identity: None,
+                        server_name: String::new(),
+                        url: link.clone(),
+                    });
+                    checked.push(link);
+                }
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs.into_iter().rev().collect::<Vec<_>>()));
+        let options = RequestOptions {
+            connect_timeout: TIMEOUT,
+            read_timeout: TIMEOUT,
+            ..options
+        };
+        let mut cancel = vec![];
+        for _ in 0..WORKERS.min(checked.len()) {
+            let jobs = jobs.clone();
+            let sender = sender.clone();
+            let known_hosts = known_hosts.clone();
+            let worker_cancel = CancelHandle::default();
+            cancel.push(worker_cancel.clone());
+            std::thread::spawn(move || {
+                while let Some(job) = jobs.lock().unwrap().pop() {
+                    let res = if job.server_name.is_empty() {
+                        Err(format!("'{}' isn't a valid URL", job.url))
+                    } else {
+                        let res = request_header(
+                            &job.server_name,
+                            job.url.as_bytes(),
+                            &known_hosts,
+                            job.identity.as_ref(),
+                            options,
+                            &worker_cancel,
+                        );
+                        forget_mismatch(&known_hosts, &job.server_name);
+                        res.map_err(|e| e.to_string())
+                    };
+                    // The check was dropped, the remaining links don't matter anymore
+                    if sender.send((job.index, res)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Self {
+            page_url: page_url.to_string(),
+            results: vec![None; checked.len()],
+            links: checked,
+            skipped,
+            receiver,
+            cancel,
+        }
+    }
+
+    /// Stores the results that arrived, returns whether there were any
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok((index, res)) => {
+                    self.results[index] = Some(res);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break changed,
+            }
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.results.iter().all(Option::is_some)
+    }
+
+    /// Report listing the links that didn't answer with a success, then the ones that did
+    pub fn to_gemtext(&self) -> Gemtext {
+        let mut data = vec![
+            GemtextEntry::MajorHeading("Link check".to_string()),
+            GemtextEntry::Link {
+                url: self.page_url.clone(),
+                label: "Checked page".to_string(),
+            },
+        ];
+        let checked: Vec<_> = self
+            .links
+            .iter()
+            .zip(&self.results)
+            .filter_map(|(url, res)| Some((url, res.as_ref()?)))
+            .collect();
+        let (working, broken): (Vec<_>, Vec<_>) = checked
+            .iter()
+            .partition(|(_, res)| res.as_ref().is_ok_and(|header| header.code / 10 == 2));
+        data.push(GemtextEntry::Text(if self.done() {
+            format!(
+                "{} of {} links didn't answer with a success.",
+                broken.len(),
+                self.links.len()
+            )
+        } else {
+            format!("Checked {} of {} links...", checked.len(), self.links.len())
+        }));
+        if self.skipped > 0 {
+            data.push(GemtextEntry::Text(format!(
+                "{} links to other protocols weren't checked.",
+                self.skipped
+            )));
+        }
+        for (heading, links) in [("Problems", broken), ("Working", working)] {
+            if links.is_empty() {
+                continue;
+            }
+            data.push(GemtextEntry::MediumHeading(heading.to_string()));
+            data.extend(links.into_iter().map(|(url, res)| {
+                GemtextEntry::Link {
+                    url: url.to_string(),
+                    label: match res {
+                        Ok(header) => format!("{} {} {}", header.code, header.class(), header.meta)
+                            .trim_end()
+                            .to_string(),
+                        Err(e) => format!("Error: {e}"),
+                    },
+                }
+            }));
+        }
+        Gemtext { data }
+    }
+}
+
+impl Drop for LinkCheck {
+    fn drop(&mut self) {
+        for cancel in &self.cancel {
+            cancel.cancel();
+        }
+    }
+}
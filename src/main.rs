@@ -1,51 +1,339 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-mod gemtext;
-mod response;
-mod verifier;
+mod about;
+mod args;
+mod bookmarks;
+mod cache;
+mod find;
+mod linkcheck;
+mod page_layout;
+mod settings;
+mod subscriptions;
+mod theme;
 use std::{
-    error::Error,
-    io::{Read, Write},
-    net::TcpStream,
+    collections::{HashMap, HashSet},
+    io::Write,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use eframe::egui::{self, Color32, Key, RichText};
-use gemtext::{Gemtext, GemtextEntry};
-use response::{GeminiResponse, PermanentFailureKind, RedirectionKind};
-use rustls::RootCertStore;
-use verifier::GeminiCertVerifier;
-
-const BG_COLOR: Color32 = Color32::from_rgb(40, 44, 52);
-const HOVERED_BG_COLOR: Color32 = Color32::from_rgb(48, 54, 64);
-const PREFORMATTED_BG_COLOR: Color32 = Color32::from_rgb(25, 27, 31);
-const RED_COLOR: Color32 = Color32::from_rgb(190, 96, 105);
-const TEXT_COLOR: Color32 = Color32::from_rgb(171, 178, 191);
-const PREFORMATTED_TEXT_COLOR: Color32 = Color32::from_rgb(156, 163, 176);
-const LINK_COLOR: Color32 = Color32::from_rgb(86, 182, 194);
-const BRIGHT_TEXT_COLOR: Color32 = Color32::from_rgb(201, 208, 221);
+use about::AboutPage;
+use args::Args;
+use bookmarks::Bookmark;
+use cache::{Cache, CachedPage};
+use eframe::egui::{self, Color32, FontId, Key, RichText, TextFormat};
+use find::Find;
+use linkcheck::LinkCheck;
+use page_layout::{LayoutKey, PageLayout, RowHeights};
+use remi::{
+    gemtext::{self, Gemtext, GemtextEntry},
+    identity::{CertScope, ClientIdentity, Identities},
+    protocol::Protocol,
+    request::{
+        download, forget_mismatch, request, upload, CancelHandle, RequestError, RequestOptions,
+        RequestResult,
+    },
+    response::{
+        self, GeminiResponse, GeminiResponseParseError, InputKind, PermanentFailureKind,
+        RedirectionKind, ResponseHeader, SuccessBody, TemporaryFailureKind,
+    },
+    url::{self, decode_for_display, percent_decode, percent_encode, split_host_port, GeminiUrl},
+    verifier::{CertificateMismatch, KnownHosts, VerificationMode},
+};
+use settings::{
+    Settings, MAX_READER_WIDTH, MAX_TIMEOUT, MAX_ZOOM, MIN_READER_WIDTH, MIN_ZOOM, ZOOM_STEP,
+};
+use subscriptions::Subscription;
+use theme::Theme;
 
 const TEXT_SIZE: f32 = 20.;
 const MINOR_SIZE: f32 = 30.;
 const MEDIUM_SIZE: f32 = 40.;
 const MAJOR_SIZE: f32 = 50.;
+/// Maximum width and height of a decoded image, in pixels
+const MAX_IMAGE_SIZE: u32 = 8192;
 
 const DEFAULT_SERVER: &str = "geminiprotocol.net";
 const DEFAULT_URL: &str = "gemini://geminiprotocol.net/";
 
+const MAX_REDIRECTS: usize = 5;
+/// Seconds waited before retrying after a slow down response without a valid delay
+const DEFAULT_SLOW_DOWN_DELAY: u64 = 5;
+/// Longest delay a slow down response can make the browser wait, in seconds
+const MAX_SLOW_DOWN_DELAY: u64 = 120;
+/// Link schemes that are opened with the system's default handler
+const EXTERNAL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+/// Hover text of plain http links found on encrypted pages
+const INSECURE_LINK_HINT: &str = "Unencrypted link, opens outside of remi over plain http";
+
 const BOOKMARKS_STORE_KEY: &str = "bookmarks";
+const KNOWN_HOSTS_STORE_KEY: &str = "known_hosts";
+/// History of the single page that was kept before there were tabs, only read to migrate it
+const HISTORY_STORE_KEY: &str = "history";
+const TABS_STORE_KEY: &str = "tabs";
+const SUBSCRIPTIONS_STORE_KEY: &str = "subscriptions";
+/// Number of history entries kept across sessions, per tab
+const MAX_STORED_HISTORY: usize = 200;
+/// Number of characters of a tab title shown in the tab strip
+const MAX_TAB_TITLE_LEN: usize = 24;
+/// Larger `/favicon.txt` responses can't hold a single emoji and are ignored
+const MAX_FAVICON_RESPONSE_SIZE: usize = 1024;
+/// Exit status of `--fetch` when no response was received
+const FETCH_FAILED_STATUS: i32 = 7;
 
 struct App {
+    tabs: Vec<Tab>,
+    active_tab: usize,
+    bookmarks: Vec<Bookmark>,
+    subscriptions: Vec<Subscription>,
+    /// Responses of the subscribed pages being checked for new entries
+    feed_updates: Option<Receiver<FeedUpdate>>,
+    /// Emoji served by each host at `/favicon.txt`, `None` while it's fetched or if there isn't one.
+    /// Every host is only asked once per session.
+    favicons: HashMap<String, Option<String>>,
+    favicon_sender: Sender<(String, Option<String>)>,
+    favicon_receiver: Receiver<(String, Option<String>)>,
+    /// Whether the `about:stats` pages have to be rebuilt once every tab was updated
+    refresh_stats: bool,
+    /// Whether the bookmarks menu shows the editing controls
+    editing_bookmarks: bool,
+    new_folder_name: String,
+    settings: Settings,
+    show_toc: bool,
+    /// Preformatted blocks folded by the user, as the URL of their page and their entry index.
+    /// They are only remembered for the session.
+    folded_blocks: HashSet<(String, usize)>,
+    /// Whether long text lines wrap instead of scrolling horizontally, only kept for the session
+    wrap_text: bool,
+    link_number: String,
+    known_hosts: Arc<Mutex<KnownHosts>>,
+    identities: Identities,
+    /// Scope of the identities set up on the client certificate page
+    identity_scope: CertScope,
+    /// Responses of visited pages, offered when they can't be loaded
+    cache: Cache,
+    /// Stored settings overridden by command line flags for the session, as `(stored, flag)`.
+    /// See `saved_setting`.
+    home_url_override: Option<(String, String)>,
+    verification_override: Option<(VerificationMode, VerificationMode)>,
+}
+
+/// A page being browsed, with its own history and request
+struct Tab {
+    /// Identifies the tab's widgets, so that each tab keeps its own scroll position
+    id: egui::Id,
     server_name: String,
     request_data: String,
     url_bar_data: String,
-    gemtext: Gemtext,
-    bookmarks: Vec<String>,
+    page: Page,
+    /// Counts the pages shown, see `set_page`
+    page_generation: u64,
+    /// Source of the page when it is Gemtext received from a server
+    gemtext_source: String,
+    /// Raw bytes of the response shown, header line included
+    raw_response: Vec<u8>,
+    /// Whether the raw response is shown instead of the rendered page
+    view_source: bool,
+    /// Header of the last response received, shown in the status bar
+    status: Option<ResponseHeader>,
+    /// `lang` parameter of the page's meta, if it has one
+    lang: Option<String>,
     moving_in_history: bool,
-    history: Vec<(String, String)>,
+    history: History,
     history_index: usize,
+    /// Index of the history entry that is currently shown
+    loaded_history_index: usize,
     redir: bool,
+    loading: Option<Loading>,
+    /// URLs that redirected in the chain currently being followed
+    redirects: Vec<String>,
+    /// Request that was temporarily redirected in the current chain, it stays the page's canonical URL
+    redirect_origin: Option<(String, String)>,
+    /// When the current request is sent again after the server asked to slow down
+    retry_at: Option<Instant>,
+    /// Redirection to another host waiting for the user's confirmation
+    pending_redirect: Option<PendingRedirect>,
+    /// Plain http link waiting for the user's confirmation before it's opened outside of remi
+    pending_insecure_link: Option<String>,
+    /// Fragment of the loaded URL, the matching heading is scrolled to once the page is shown
+    scroll_fragment: Option<String>,
+    /// Index of the heading entry picked in the table of contents
+    scroll_to_heading: Option<usize>,
+    find: Option<Find>,
+    links: Vec<String>,
+    page_scroll: PageScroll,
+    /// Heights of the entries of the Gemtext page, only the ones in view are rendered
+    page_layout: PageLayout,
+    /// Resolved target of the link under the mouse, shown in the status bar instead of the status
+    hovered_link: Option<String>,
+    pending_input: Option<PendingInput>,
+    pending_upload: Option<PendingUpload>,
+    /// Cached response of the page that couldn't be loaded, offered on the error page
+    offline_copy: Option<CachedPage>,
+    /// When the cached response shown instead of the page was received
+    cached_at: Option<SystemTime>,
+    /// Check of the links of the page, whose report replaces the page while it runs
+    link_check: Option<LinkCheck>,
+    cert_mismatch: Option<CertificateMismatch>,
+    /// Whether the page changed and should be shown from the top
+    reset_scroll: bool,
+    /// Set on tabs restored from the last session until their first response
+    restoring: bool,
+}
+
+/// Pages visited in a tab, as `(server_name, request_data)` pairs
+type History = Vec<(String, String)>;
+
+/// Response to the request of a subscribed page, with the page's URL
+type FeedUpdate = (String, RequestResult<(GeminiResponse, Vec<u8>)>);
+
+/// A request running on a worker thread
+struct Loading {
+    receiver: Receiver<RequestResult<(GeminiResponse, Vec<u8>)>>,
+    cancel: CancelHandle,
+}
+
+impl Loading {
+    fn start(
+        server_name: String,
+        request_data: String,
+        known_hosts: Arc<Mutex<KnownHosts>>,
+        identity: Option<ClientIdentity>,
+        options: RequestOptions,
+    ) -> Self {
+        Self::spawn(move |cancel| {
+            Protocol::from_url(&request_data).request(
+                &server_name,
+                &request_data,
+                &known_hosts,
+                identity.as_ref(),
+                options,
+                cancel,
+            )
+        })
+    }
+
+    /// Uploads `body` to the Titan URL `url`
+    fn upload(
+        server_name: String,
+        url: String,
+        body: Vec<u8>,
+        token: String,
+        known_hosts: Arc<Mutex<KnownHosts>>,
+        identity: Option<ClientIdentity>,
+        options: RequestOptions,
+    ) -> Self {
+        Self::spawn(move |cancel| {
+            upload(
+                &server_name,
+                &url,
+                &body,
+                &token,
+                &known_hosts,
+                identity.as_ref(),
+                options,
+                cancel,
+            )
+        })
+    }
+
+    /// Yields `raw`, a response received earlier, as if it was just received
+    fn cached(raw: Vec<u8>) -> Self {
+        Self::spawn(move |_| Ok((GeminiResponse::from_bytes(&raw)?, raw)))
+    }
+
+    fn spawn(
+        work: impl FnOnce(&CancelHandle) -> RequestResult<(GeminiResponse, Vec<u8>)> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = CancelHandle::default();
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            // The receiver is gone if the request was cancelled, nothing to do then
+            let _ = sender.send(work(&worker_cancel));
+        });
+        Self { receiver, cancel }
+    }
+
+    fn poll(&self) -> Option<RequestResult<(GeminiResponse, Vec<u8>)>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("request worker stopped".into())),
+        }
+    }
+}
+
+/// Content of the currently loaded page
+enum Page {
+    Gemtext(Gemtext),
+    Text(String),
+    Binary {
+        mime: String,
+        data: Vec<u8>,
+    },
+    /// An `image/*` response, `texture` holds the decoding error if it couldn't be shown
+    Image {
+        mime: String,
+        data: Vec<u8>,
+        texture: Result<egui::TextureHandle, String>,
+    },
+    Error {
+        title: String,
+        msg: String,
+        /// Shown in a monospace font below `msg`
+        detail: Option<String>,
+    },
+    ClientCertificate {
+        title: String,
+        msg: String,
+    },
+    /// `about:settings`, drawn with widgets by `App::settings_ui`
+    Settings,
+}
+
+/// Input requested by the server with a 1x response, submitted as the query of `url`
+struct PendingInput {
+    kind: InputKind,
+    prompt: String,
+    url: String,
+    text: String,
+}
+
+/// Content to upload to the Titan URL `url`, sent once the user submits it
+struct PendingUpload {
+    url: String,
+    text: String,
+    /// Optional token some capsules require to accept uploads
+    token: String,
+}
+
+/// Change to the bookmarks requested from the bookmarks menu
+enum BookmarkAction {
+    Open(usize),
+    Remove(usize),
+    Swap(usize, usize),
+    Move(usize, Option<String>),
+}
+
+/// Vertical scroll state of the page as of the last frame, used to scroll with the keyboard
+#[derive(Debug, Default, Clone, Copy)]
+struct PageScroll {
+    offset: f32,
+    viewport_height: f32,
+    content_height: f32,
+}
+
+/// Redirection from `from` to a different host, followed once the user confirms it
+struct PendingRedirect {
+    from: String,
+    server_name: String,
+    request_data: String,
 }
 
 static OUT: Mutex<String> = Mutex::new(String::new());
@@ -68,32 +356,41 @@ macro_rules! remilog {
 }
 
 fn main() -> eframe::Result {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", args::USAGE);
+            return Ok(());
+        }
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("remi: {e}\n\n{}", args::USAGE);
+            std::process::exit(2);
+        }
+    };
     rustls::crypto::ring::default_provider()
         .install_default()
         .unwrap();
-    let mut server_name = DEFAULT_SERVER.to_string(); // make sure this isn't dropped
-    let mut request_data = DEFAULT_URL.to_string();
-    let url_bar_data = request_data.clone();
-    let gemtext = Gemtext {
-        data: vec![GemtextEntry::Text(
-            "You shouldn't be seeing this".to_string(),
-        )],
-    };
-    let mut bookmarks = Vec::<String>::new();
-    let history = Vec::<(String, String)>::new();
-    let history_index = 0;
-    let mut redir = true;
-    let moving_in_history = false;
-    {
-        let mut args = std::env::args();
-        args.next();
-        if let Some(url) = args.next() {
-            redir = redirect(&mut server_name, &mut request_data, &url);
-        }
+    if let Some(url) = args.fetch {
+        std::process::exit(fetch_to_stdout(&url, args.insecure));
     }
+    let mut bookmarks = Vec::<Bookmark>::new();
+    let mut subscriptions = Vec::<Subscription>::new();
+    let custom_theme = load_custom_theme();
+    let mut settings = Settings::new(custom_theme);
+    let mut stored_tabs = vec![];
+    let mut active_tab = 0;
+    let link_number = String::new();
+    let (favicon_sender, favicon_receiver) = mpsc::channel();
+    let mut known_hosts = KnownHosts::default();
+    let identities =
+        Identities::load(eframe::storage_dir("remi").map(|dir| dir.join("identities")));
+    let start_urls = args.urls;
+    let mut home_url_override = None;
+    let mut verification_override = None;
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([args.width.unwrap_or(800.0), args.height.unwrap_or(600.0)]),
         ..Default::default()
     };
 
@@ -101,386 +398,3939 @@ fn main() -> eframe::Result {
         "remi",
         options,
         Box::new(move |cc| {
+            // Ctrl+Plus/Minus zoom the page instead of the whole interface
+            cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
             if let Some(storage) = cc.storage {
                 if let Some(bookmarks_raw) = storage.get_string(BOOKMARKS_STORE_KEY) {
-                    bookmarks = bookmarks_raw.lines().map(|l| l.to_string()).collect();
+                    bookmarks = bookmarks::from_lines(&bookmarks_raw);
+                }
+                if let Some(subscriptions_raw) = storage.get_string(SUBSCRIPTIONS_STORE_KEY) {
+                    subscriptions = subscriptions::from_lines(&subscriptions_raw);
+                }
+                if let Some(known_hosts_raw) = storage.get_string(KNOWN_HOSTS_STORE_KEY) {
+                    known_hosts = KnownHosts::from_lines(&known_hosts_raw);
+                }
+                settings = Settings::load(storage, custom_theme);
+                // Otherwise the home page is opened, as on the first run
+                if settings.restore_session {
+                    if let Some(tabs_raw) = storage.get_string(TABS_STORE_KEY) {
+                        (stored_tabs, active_tab) = tabs_from_lines(&tabs_raw);
+                    } else if let Some(history_raw) = storage.get_string(HISTORY_STORE_KEY) {
+                        stored_tabs.push(history_from_lines(&history_raw));
+                    }
+                }
+            }
+            if let Some(home) = args.home {
+                home_url_override = Some((
+                    std::mem::replace(&mut settings.home_url, home.clone()),
+                    home,
+                ));
+            }
+            if args.insecure {
+                let trust_all = VerificationMode::TrustAll;
+                let stored =
+                    std::mem::replace(&mut settings.request_options.verification, trust_all);
+                verification_override = Some((stored, trust_all));
+            }
+            let mut tabs: Vec<_> = stored_tabs
+                .into_iter()
+                .map(|(history, history_index)| Tab::restore(history, history_index))
+                .collect();
+            let mut opened = false;
+            for url in start_urls {
+                let tab = Tab::open(&url);
+                if !tab.redir {
+                    eprintln!("remi: skipping '{url}', it isn't a valid Gemini URL");
+                    continue;
                 }
+                if !opened {
+                    active_tab = tabs.len();
+                    opened = true;
+                }
+                tabs.push(tab);
+            }
+            if tabs.is_empty() {
+                tabs.push(Tab::home(&settings.home_url));
             }
             Ok(Box::new(App {
-                server_name,
-                request_data,
-                url_bar_data,
-                gemtext,
+                tabs,
+                active_tab,
                 bookmarks,
-                moving_in_history,
-                history,
-                history_index,
-                redir,
+                subscriptions,
+                feed_updates: None,
+                favicons: HashMap::new(),
+                refresh_stats: false,
+                favicon_sender,
+                favicon_receiver,
+                editing_bookmarks: false,
+                new_folder_name: String::new(),
+                show_toc: false,
+                folded_blocks: HashSet::new(),
+                wrap_text: true,
+                link_number,
+                known_hosts: Arc::new(Mutex::new(known_hosts)),
+                identities,
+                identity_scope: CertScope::default(),
+                cache: Cache::load(
+                    eframe::storage_dir("remi").map(|dir| dir.join("cache")),
+                    settings.cache_size,
+                ),
+                settings,
+                home_url_override,
+                verification_override,
             }))
         }),
     )
 }
 
-impl eframe::App for App {
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        let mut bookmarks_raw = String::new();
-        for bookmark in &self.bookmarks {
-            bookmarks_raw.push_str(bookmark);
-            bookmarks_raw.push('\n');
+/// Requests `url` for `--fetch` and writes the body of a success to stdout, the status line goes to stderr.
+/// Text bodies are decoded, others are written as they are. Returns the exit status.
+fn fetch_to_stdout(url: &str, insecure: bool) -> i32 {
+    let options = RequestOptions {
+        // The URL was typed by the user, there's no setting to respect here
+        spartan: true,
+        verification: if insecure {
+            VerificationMode::TrustAll
+        } else {
+            VerificationMode::default()
+        },
+        ..RequestOptions::default()
+    };
+    let (response, raw) = match remi::fetch(url, options) {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("remi: {e}");
+            return FETCH_FAILED_STATUS;
         }
-        bookmarks_raw.pop();
-        storage.set_string(BOOKMARKS_STORE_KEY, bookmarks_raw);
+    };
+    if let Some(header) = ResponseHeader::from_bytes(&raw) {
+        eprintln!("{} {}", header.code, header.meta);
     }
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut reset_scroll = false;
-        if self.redir {
-            self.redir = false;
-
-            match request(&self.server_name, self.request_data.as_bytes()) {
-                Ok(response) => match response {
-                    GeminiResponse::Success { body } => {
-                        self.gemtext = Gemtext::from_str(&body)
-                            .expect("[ERROR] Data received is not valid Gemtext.");
-                        reset_scroll = true;
-                        if !self.moving_in_history {
-                            self.history.truncate(self.history_index + 1);
-                            self.history
-                                .push((self.server_name.clone(), self.request_data.clone()));
-                            self.history_index = self.history.len() - 1;
-                        } else {
-                            self.moving_in_history = false;
-                        }
-                    }
-                    GeminiResponse::PermanentFailure {
-                        kind: PermanentFailureKind::NotFound,
-                        msg,
-                    } => {
-                        remilog!(
-                            "[PERM::NOTFOUND] '{:?}' couldn't be found: '{msg}'",
-                            self.request_data
-                        );
-                        self.moving_in_history = false;
-                        self.server_name = self.history[self.history_index].0.clone();
-                        self.request_data = self.history[self.history_index].1.clone();
-                    }
-                    GeminiResponse::PermanentFailure {
-                        kind: PermanentFailureKind::General,
-                        msg,
-                    } => {
-                        remilog!(
-                            "[PERM::GENERAL] Error from server: '{msg}' with request: '{}'",
-                            self.request_data
-                        );
-                        self.moving_in_history = false;
-                        self.server_name = self.history[self.history_index].0.clone();
-                        self.request_data = self.history[self.history_index].1.clone();
-                    }
-                    GeminiResponse::Redirection {
-                        kind: RedirectionKind::Permanent,
-                        to,
-                    } => {
-                        self.redir = redirect(&mut self.server_name, &mut self.request_data, &to);
-                        if !self.redir {
-                            todo!("[TODO] Handle incorrect permanent redirection gracefully");
-                        }
-                    }
-                    _ => panic!("[ERROR] Unsupported response: {response:?}"),
-                },
-                Err(e) => {
-                    remilog!(
-                        "[REQUEST ERROR] Request error from server '{}' with request '{}': {e}",
-                        self.server_name,
-                        self.request_data
-                    );
-                    if self.history.len() == 0 {
-                        self.history
-                            .push((DEFAULT_SERVER.to_string(), DEFAULT_URL.to_string()));
-                        self.redir = true;
-                    }
-                    self.server_name = self.history[self.history_index].0.clone();
-                    self.request_data = self.history[self.history_index].1.clone();
-                }
-            }
-            self.url_bar_data = self.request_data.clone();
+    let GeminiResponse::Success { body, .. } = response else {
+        return i32::from(response.code() / 10);
+    };
+    let mut stdout = std::io::stdout().lock();
+    let written = match body {
+        SuccessBody::Text(text) => stdout.write_all(text.as_bytes()),
+        SuccessBody::Binary(data) => stdout.write_all(&data),
+    };
+    if let Err(e) = written.and_then(|_| stdout.flush()) {
+        eprintln!("remi: couldn't write the body: {e}");
+        return FETCH_FAILED_STATUS;
+    }
+    0
+}
+
+impl Tab {
+    fn new(history: History, history_index: usize) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: egui::Id::new(("tab", NEXT_ID.fetch_add(1, Ordering::Relaxed))),
+            server_name: DEFAULT_SERVER.to_string(),
+            request_data: DEFAULT_URL.to_string(),
+            url_bar_data: decode_for_display(DEFAULT_URL),
+            page: Page::Gemtext(Gemtext {
+                data: vec![GemtextEntry::Text(
+                    "You shouldn't be seeing this".to_string(),
+                )],
+            }),
+            page_generation: 0,
+            gemtext_source: String::new(),
+            raw_response: vec![],
+            view_source: false,
+            status: None,
+            lang: None,
+            moving_in_history: false,
+            history,
+            history_index,
+            loaded_history_index: history_index,
+            redir: false,
+            loading: None,
+            redirects: Vec::new(),
+            redirect_origin: None,
+            retry_at: None,
+            pending_redirect: None,
+            pending_insecure_link: None,
+            scroll_fragment: None,
+            scroll_to_heading: None,
+            find: None,
+            links: Vec::new(),
+            page_scroll: PageScroll::default(),
+            page_layout: PageLayout::default(),
+            hovered_link: None,
+            pending_input: None,
+            pending_upload: None,
+            offline_copy: None,
+            cached_at: None,
+            link_check: None,
+            cert_mismatch: None,
+            reset_scroll: false,
+            restoring: false,
         }
+    }
 
-        ctx.style_mut(|style| {
-            style.visuals.panel_fill = BG_COLOR;
-            style.visuals.window_fill = BG_COLOR;
-            style.visuals.hyperlink_color = LINK_COLOR;
-        });
+    /// New tab loading `url`
+    fn open(url: &str) -> Self {
+        let mut tab = Self::new(vec![], 0);
+        tab.navigate(url);
+        tab.url_bar_data = decode_for_display(&tab.request_data);
+        tab
+    }
 
-        egui::TopBottomPanel::top("url_bar").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui
-                    .button(RichText::new("<").size(TEXT_SIZE).color(TEXT_COLOR))
-                    .clicked()
-                {
-                    if self.history_index > 0 {
-                        self.history_index -= 1;
-                        self.moving_in_history = true;
-                    }
-                }
+    /// New tab loading the target of the link `url` found on this tab's page
+    fn open_link(&self, url: &str) -> Self {
+        let mut tab = Self::new(vec![], 0);
+        tab.server_name = self.server_name.clone();
+        tab.request_data = self.request_data.clone();
+        tab.follow_link(url);
+        tab.url_bar_data = decode_for_display(&tab.request_data);
+        tab
+    }
 
-                if ui
-                    .button(RichText::new(">").size(TEXT_SIZE).color(TEXT_COLOR))
-                    .clicked()
-                {
-                    if self.history_index + 1 < self.history.len() {
-                        self.history_index += 1;
-                        self.moving_in_history = true;
-                    }
-                }
-                ui.menu_button(RichText::new("#").size(TEXT_SIZE).color(TEXT_COLOR), |ui| {
-                    ui.set_max_width(ui.min_size().x);
-                    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
-                    ui.style_mut().visuals.widgets.inactive.weak_bg_fill = BG_COLOR;
-                    ui.style_mut().visuals.widgets.hovered.weak_bg_fill = HOVERED_BG_COLOR;
+    /// New tab loading `home_url`, or the default page if the home URL was edited into something invalid
+    fn home(home_url: &str) -> Self {
+        let mut tab = Self::open(home_url);
+        if !tab.redir {
+            tab = Self::open(DEFAULT_URL);
+        }
+        tab
+    }
 
-                    let mut bookmark_to_remove = None;
-                    for (i, bookmark) in self.bookmarks.iter().enumerate() {
-                        let response =
-                            ui.button(RichText::new(bookmark).size(TEXT_SIZE).color(TEXT_COLOR));
-                        if response.clicked() {
-                            self.redir =
-                                redirect(&mut self.server_name, &mut self.request_data, bookmark);
-                        }
-                        if response.secondary_clicked() {
-                            bookmark_to_remove = Some(i);
-                        }
-                    }
-                    if let Some(i) = bookmark_to_remove {
-                        self.bookmarks.remove(i);
-                    }
-                    ui.style_mut().wrap_mode = None;
-                });
+    /// Tab reopening the current entry of a history saved in a previous session
+    fn restore(history: History, history_index: usize) -> Self {
+        let mut tab = Self::new(history, history_index);
+        tab.moving_in_history = true;
+        tab.restoring = true;
+        tab
+    }
 
-                if ui
-                    .button(RichText::new("+").size(TEXT_SIZE).color(TEXT_COLOR))
-                    .clicked()
-                    && !self.bookmarks.contains(&self.request_data)
-                {
-                    self.bookmarks.push(self.request_data.clone())
-                }
+    /// Text shown in the tab strip, the first heading of the page or its URL
+    fn title(&self) -> String {
+        let title = self.page_title().unwrap_or(self.current_url());
+        if title.chars().count() > MAX_TAB_TITLE_LEN {
+            let mut title: String = title.chars().take(MAX_TAB_TITLE_LEN - 3).collect();
+            title.push_str("...");
+            title
+        } else {
+            title.to_string()
+        }
+    }
 
-                let available_width = ui.available_width();
-                let console_button_response =
-                    ui.menu_button(RichText::new("$").size(TEXT_SIZE).color(TEXT_COLOR), |ui| {
-                        ui.set_max_width(available_width);
-                        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
-                        ui.label(
-                            RichText::new("Console")
-                                .size(MINOR_SIZE)
-                                .color(BRIGHT_TEXT_COLOR)
-                                .underline(),
-                        );
-                        ui.label(
-                            RichText::new(OUT.lock().unwrap().as_str())
-                                .size(TEXT_SIZE / 1.5)
-                                .color(TEXT_COLOR)
-                                .monospace(),
-                        );
-                        ui.style_mut().wrap_mode = None;
-                    });
-                if console_button_response.response.secondary_clicked() {
-                    OUT.lock().unwrap().clear();
-                }
+    /// Shows `page` instead of the current one, whose layout is dropped
+    fn set_page(&mut self, page: Page) {
+        self.page = page;
+        self.page_generation += 1;
+    }
 
-                ui.style_mut().override_font_id = Some(egui::FontId {
-                    size: TEXT_SIZE,
-                    family: egui::FontFamily::Proportional,
-                });
-                let text_edit = egui::TextEdit::singleline(&mut self.url_bar_data)
-                    .text_color(BRIGHT_TEXT_COLOR)
-                    .desired_width(f32::INFINITY);
-                let lost_focus = ui.add(text_edit).lost_focus();
-                ui.style_mut().override_font_id = None;
-                if lost_focus && ui.input(|i| i.key_pressed(Key::Enter)) {
-                    self.redir = redirect(
-                        &mut self.server_name,
-                        &mut self.request_data,
-                        &self.url_bar_data,
-                    );
-                    if !self.redir {
-                        self.url_bar_data = self.request_data.clone();
-                    }
-                };
-            })
+    fn show_error(&mut self, title: String, msg: String) {
+        self.set_page(Page::Error {
+            title,
+            msg,
+            detail: None,
         });
-        if self.moving_in_history {
-            let (new_sn, new_data) = self.history[self.history_index].clone();
-            self.server_name = new_sn;
-            self.request_data = new_data;
+        self.raw_response.clear();
+        self.moving_in_history = false;
+    }
+
+    /// Shows the server's message for a 51 and points the request back at the page that was shown.
+    /// On the first navigation of the tab there's no such page, the default page is used instead.
+    fn show_not_found(&mut self, msg: &str) {
+        let mut details = format!(
+            "'{}' couldn't be found.",
+            percent_decode(&self.request_data)
+        );
+        if !msg.trim().is_empty() {
+            details.push_str(&format!("\nThe server said: {}", msg.trim()));
+        }
+        self.show_error("Not found".to_string(), details);
+        self.history_index = self.loaded_history_index;
+        (self.server_name, self.request_data) = self
+            .history
+            .get(self.history_index)
+            .cloned()
+            .unwrap_or_else(|| (DEFAULT_SERVER.to_string(), DEFAULT_URL.to_string()));
+    }
+
+    /// Points the current request back at the page that is shown, or the default page if there isn't one.
+    /// A move in history that didn't finish is abandoned.
+    fn restore_from_history(&mut self) {
+        self.history_index = self.loaded_history_index;
+        let Some((server_name, request_data)) = self.history.get(self.history_index) else {
+            // Recorded in history once it's loaded, like any new page
+            self.server_name = DEFAULT_SERVER.to_string();
+            self.request_data = DEFAULT_URL.to_string();
             self.redir = true;
+            return;
+        };
+        self.server_name = server_name.clone();
+        self.request_data = request_data.clone();
+    }
+
+    /// Requests `url` as a new page, `redir` is only set if it's a valid URL.
+    /// The page is recorded in history after the one that is shown, dropping the pages after it,
+    /// even if it was requested while moving in history.
+    fn navigate(&mut self, url: &str) {
+        self.redir = redirect(&mut self.server_name, &mut self.request_data, url);
+        if self.redir {
+            self.moving_in_history = false;
+            self.history_index = self.loaded_history_index;
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.style_mut().visuals.widgets.inactive.weak_bg_fill = BG_COLOR;
-            ui.style_mut().visuals.widgets.hovered.weak_bg_fill = BG_COLOR;
-            let mut scroll_area = egui::ScrollArea::vertical()
-                .auto_shrink(false)
-                .stick_to_right(true);
-            if reset_scroll {
-                scroll_area = scroll_area.scroll_offset(egui::Vec2 { x: 0., y: 0. })
+    /// Follows a link of the current page, links with a scheme other than gemini are handed to the system.
+    /// Plain http links of encrypted pages are only opened once the user confirms it.
+    fn follow_link(&mut self, url: &str) {
+        match url::scheme(url) {
+            _ if is_insecure_link(self.current_url(), url) => {
+                self.pending_insecure_link = Some(url.to_string());
             }
-            scroll_area.show(ui, |ui| {
-                ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Wrap);
-                for (i, g) in self.gemtext.data.iter().enumerate() {
-                    match g {
-                        gemtext::GemtextEntry::Text(t) => {
-                            ui.label(RichText::new(t).size(TEXT_SIZE).color(TEXT_COLOR));
-                        }
-                        gemtext::GemtextEntry::Link { url, label } => {
-                            ui.horizontal(|ui| {
-                                let text = RichText::new(url).size(TEXT_SIZE).color(LINK_COLOR);
-                                let response = ui.link(text.clone());
-                                if response.clicked() {
-                                    self.redir = redirect(
-                                        &mut self.server_name,
-                                        &mut self.request_data,
-                                        url,
-                                    );
-                                }
-                                ui.label(RichText::new(label).size(TEXT_SIZE).color(TEXT_COLOR))
-                            });
-                        }
-                        gemtext::GemtextEntry::MinorHeading(h) => {
-                            ui.label(RichText::new(h).size(MINOR_SIZE).color(RED_COLOR));
-                        }
-                        gemtext::GemtextEntry::MediumHeading(h) => {
-                            ui.label(RichText::new(h).size(MEDIUM_SIZE).color(RED_COLOR));
-                        }
-                        gemtext::GemtextEntry::MajorHeading(h) => {
-                            ui.label(RichText::new(h).size(MAJOR_SIZE).color(RED_COLOR));
-                        }
-                        gemtext::GemtextEntry::List(elems) => {
-                            for el in elems {
-                                ui.label(
-                                    RichText::new(format!("* {el}"))
-                                        .size(TEXT_SIZE)
-                                        .color(BRIGHT_TEXT_COLOR),
-                                );
-                            }
-                        }
-                        gemtext::GemtextEntry::Quote(q) => {
-                            ui.label(
-                                RichText::new(q)
-                                    .size(TEXT_SIZE)
-                                    .color(TEXT_COLOR)
-                                    .background_color(PREFORMATTED_BG_COLOR),
-                            );
-                        }
-                        gemtext::GemtextEntry::Preformatted { alt_text: _, body } => {
-                            ui.push_id(i, |ui| {
-                                // Idea taken from egui::TextEdit::show
-                                let where_to_put_background = ui.painter().add(egui::Shape::Noop);
-                                let output = egui::ScrollArea::horizontal()
-                                    .auto_shrink([false, true])
-                                    .drag_to_scroll(false)
-                                    .min_scrolled_width(0.)
-                                    .scroll_bar_visibility(
-                                        egui::scroll_area::ScrollBarVisibility::AlwaysVisible,
-                                    )
-                                    .show(ui, |ui| {
-                                        ui.add(
-                                            egui::Label::new(
-                                                RichText::new(body)
-                                                    .monospace()
-                                                    .size(TEXT_SIZE)
-                                                    .color(PREFORMATTED_TEXT_COLOR),
-                                            )
-                                            .selectable(true)
-                                            .extend(),
-                                        )
-                                    });
-                                let rect = output.inner.rect.with_max_x(output.inner_rect.max.x);
-                                ui.painter().set(
-                                    where_to_put_background,
-                                    egui::epaint::RectShape::filled(rect, 0, PREFORMATTED_BG_COLOR),
-                                );
-                            });
-                        }
-                    }
-                }
-                ui.style_mut().wrap_mode = None;
-            });
-        });
+            Some(scheme) if EXTERNAL_SCHEMES.contains(&scheme.as_str()) => self.open_external(url),
+            Some(scheme)
+                if !matches!(scheme.as_str(), "gemini" | "titan" | "spartan" | "about") =>
+            {
+                self.show_error(
+                    "Unsupported protocol".to_string(),
+                    format!("remi can't open '{scheme}' links:\n{url}"),
+                );
+            }
+            _ => self.navigate(url),
+        }
     }
-}
 
-pub fn request(server_name: &str, data: &[u8]) -> Result<GeminiResponse, Box<dyn Error>> {
-    let recv = request_raw(server_name, data)?;
-    Ok(GeminiResponse::from_bytes(&recv)?)
-}
+    /// Opens `url` with the system's default handler
+    fn open_external(&mut self, url: &str) {
+        remilog!("[LINK] Opening '{url}' outside of remi.");
+        if let Err(e) = open::that_detached(url) {
+            remilog!("[LINK ERROR] Couldn't open '{url}': {e}");
+            self.show_error("Couldn't open link".to_string(), format!("{url}\n{e}"));
+        }
+    }
 
-pub fn request_raw(server_name: &str, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    let server_name = unsafe {
-        std::str::from_utf8(std::slice::from_raw_parts(
-            server_name.as_ptr(),
-            server_name.len(),
-        ))
-        .unwrap()
-    };
-    let mut config = rustls::ClientConfig::builder()
-        .with_root_certificates(RootCertStore::empty())
-        .with_no_client_auth();
-    // Completely disables all verification
-    config
-        .dangerous()
-        .set_certificate_verifier(Arc::new(GeminiCertVerifier {}));
-    let (server_name, port) = {
-        if let Some(split) = server_name.split_once(':') {
-            split
+    /// Records the page that was just shown, unless it was reached by moving in history
+    fn push_history(&mut self, entry: (String, String)) {
+        if !self.moving_in_history {
+            // Loading the page that is shown again keeps the pages after it
+            if self.history.get(self.history_index) != Some(&entry) {
+                self.history.truncate(self.history_index + 1);
+                self.history.push(entry);
+                self.history_index = self.history.len() - 1;
+            }
         } else {
-            (server_name, "1965")
+            self.moving_in_history = false;
         }
-    };
-    let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name.try_into()?)?;
-    let mut sock = TcpStream::connect(&format!("{server_name}:{port}"))?;
+        self.loaded_history_index = self.history_index;
+    }
+
+    /// Saves the content of the page that is shown, Gemtext is saved as its source
+    fn save_page(&self) {
+        match &self.page {
+            Page::Gemtext(gemtext) if self.gemtext_source.is_empty() => {
+                save_to_file(&self.request_data, gemtext.to_string().as_bytes())
+            }
+            Page::Gemtext(_) => save_to_file(&self.request_data, self.gemtext_source.as_bytes()),
+            Page::Text(body) => save_to_file(&self.request_data, body.as_bytes()),
+            Page::Binary { data, .. } | Page::Image { data, .. } => {
+                save_to_file(&self.request_data, data)
+            }
+            Page::Error { .. } | Page::ClientCertificate { .. } | Page::Settings => {
+                remilog!("[SAVE ERROR] There is no content to save on this page.")
+            }
+        }
+    }
+
+    /// Text of the first heading of the page that is shown
+    fn page_title(&self) -> Option<&str> {
+        let Page::Gemtext(gemtext) = &self.page else {
+            return None;
+        };
+        gemtext.headings().next().map(|(_, h)| h.trim())
+    }
+
+    /// URL of the page that is shown, as recorded in history.
+    /// It may differ from `request_data` after a temporary redirection.
+    fn current_url(&self) -> &String {
+        self.history
+            .get(self.loaded_history_index)
+            .map_or(&self.request_data, |(_, data)| data)
+    }
+
+    fn cancel_loading(&mut self) {
+        if let Some(loading) = self.loading.take() {
+            loading.cancel.cancel();
+            self.redirects.clear();
+            self.redirect_origin = None;
+        }
+    }
+
+    /// Aborts the current request and goes back to the page that is shown
+    fn stop(&mut self) {
+        self.cancel_loading();
+        self.retry_at = None;
+        if self.moving_in_history {
+            self.moving_in_history = false;
+            self.history_index = self.loaded_history_index;
+        }
+        if !self.history.is_empty() {
+            self.restore_from_history();
+        }
+        self.url_bar_data = decode_for_display(&self.request_data);
+    }
+
+    /// Requests the page that was shown before the error page again, or the default page if there wasn't one.
+    /// A retry that was waiting is dropped, and a move in history that failed is abandoned.
+    fn leave_error(&mut self) {
+        self.restore_from_history();
+        self.redir = true;
+    }
+
+    /// Sends the request of the page that failed to load again, as it is
+    fn retry(&mut self) {
+        self.cancel_loading();
+        self.retry_at = None;
+        // The page is already in the history if it failed while moving in it
+        self.moving_in_history =
+            self.history
+                .get(self.history_index)
+                .is_some_and(|(server_name, request_data)| {
+                    *server_name == self.server_name && *request_data == self.request_data
+                });
+        self.redir = true;
+    }
+
+    fn go_back(&mut self) {
+        if self.history_index > 0 {
+            self.cancel_loading();
+            self.history_index -= 1;
+            self.moving_in_history = true;
+        }
+    }
+
+    /// Parent directory of the page that is shown, see `GeminiUrl::parent`
+    fn parent_url(&self) -> Option<String> {
+        let url = GeminiUrl::parse(self.current_url()).ok()?;
+        Some(url.parent()?.to_string())
+    }
+
+    fn go_up(&mut self) {
+        if let Some(url) = self.parent_url() {
+            self.navigate(&url);
+        }
+    }
 
-    let mut tls = rustls::Stream::new(&mut conn, &mut sock);
-    let mut data = std::str::from_utf8(data)?.trim_end().to_string();
-    data.push('\r');
-    data.push('\n');
-    tls.write_all(data.as_bytes())?;
-    let mut recv = Vec::new();
-    tls.read_to_end(&mut recv)?;
-    Ok(recv)
+    fn go_forward(&mut self) {
+        if self.history_index + 1 < self.history.len() {
+            self.cancel_loading();
+            self.history_index += 1;
+            self.moving_in_history = true;
+        }
+    }
 }
 
-pub fn redirect(server_name: &mut String, request_data: &mut String, url: &str) -> bool {
-    if url.contains("://") {
-        if url.starts_with("gemini://") {
-            if url.len() <= 9 {
-                remilog!("[REDIRECT ERROR] '{url}' is invalid.");
-                return false;
-            } else {
-                *server_name = url[9..].to_string();
-                while server_name.contains('/') {
-                    server_name.pop();
-                }
-                *request_data = url.to_string();
-                return true;
-            }
-        } else {
-            remilog!("[REDIRECT ERROR] '{url}' contains unsupported protocol.");
-            return false;
+impl App {
+    /// Opens a tab on the home page and switches to it
+    fn new_tab(&mut self) {
+        self.tabs.push(Tab::home(&self.settings.home_url));
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    /// Closes the tab at `i`, the last tab is replaced by a new one on the home page
+    fn close_tab(&mut self, i: usize) {
+        let mut tab = self.tabs.remove(i);
+        tab.cancel_loading();
+        if self.tabs.is_empty() {
+            self.tabs.push(Tab::home(&self.settings.home_url));
         }
-    } else if url.starts_with("/") {
-        *request_data = format!("gemini://{server_name}{url}");
-        return true;
-    } else if url.ends_with(".gmi") {
-        if request_data.ends_with(".gmi") {
-            *request_data = request_data.trim_end_matches(|c| c != '/').to_string();
+        if i < self.active_tab {
+            self.active_tab -= 1;
         }
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+    }
 
-        request_data.push_str(&url);
-        return true;
-    } else {
-        if !request_data.ends_with('/') {
-            request_data.push('/');
+    /// Sends the requests of `tab` and handles their responses, whether the tab is shown or not
+    fn update_loading(&mut self, tab: &mut Tab, ctx: &egui::Context) {
+        // A redirection that is being followed or confirmed must not be replaced by the history entry
+        if tab.moving_in_history
+            && tab.loading.is_none()
+            && !tab.redir
+            && tab.pending_redirect.is_none()
+        {
+            let (new_sn, new_data) = tab.history[tab.history_index].clone();
+            tab.server_name = new_sn;
+            tab.request_data = new_data;
+            tab.redir = true;
         }
-        request_data.push_str(&url);
-        return true;
+        if let Some(retry_at) = tab.retry_at {
+            let now = Instant::now();
+            if now >= retry_at {
+                tab.redir = true;
+            } else {
+                // Also keeps the countdown in the status bar ticking
+                ctx.request_repaint_after((retry_at - now).min(Duration::from_secs(1)));
+            }
+        }
+        if tab.redir {
+            tab.redir = false;
+            // Only the first request of a restored tab can fall back to the home page
+            let restoring = std::mem::take(&mut tab.restoring);
+            tab.retry_at = None;
+            tab.pending_input = None;
+            tab.pending_upload = None;
+            tab.link_check = None;
+            tab.offline_copy = None;
+            tab.cached_at = None;
+            tab.cancel_loading();
+            // Internal pages never reach the network
+            if let Some(name) = about::page_name(&tab.request_data) {
+                if name == "subscriptions" {
+                    self.check_subscriptions();
+                }
+                // The other tabs aren't available while a tab is updated
+                self.refresh_stats |= name == "stats";
+                let page = self.about_page(name, tab);
+                tab.set_page(page);
+                tab.gemtext_source.clear();
+                tab.raw_response.clear();
+                tab.status = None;
+                tab.lang = None;
+                tab.reset_scroll = true;
+                tab.push_history((tab.server_name.clone(), tab.request_data.clone()));
+                tab.url_bar_data = tab.request_data.clone();
+            } else if url::scheme(&tab.request_data).is_some_and(|scheme| scheme == "titan") {
+                // Nothing is sent until the content to upload is submitted
+                tab.pending_upload = Some(PendingUpload {
+                    url: tab.request_data.clone(),
+                    text: String::new(),
+                    token: String::new(),
+                });
+                tab.reset_scroll = true;
+                tab.moving_in_history = false;
+                tab.url_bar_data = decode_for_display(&tab.request_data);
+            } else if Protocol::from_url(&tab.request_data) == Protocol::Spartan
+                && !self.settings.request_options.spartan
+            {
+                tab.show_error(
+                    "Spartan support is turned off".to_string(),
+                    format!(
+                        "Turn on Spartan support in the settings to open '{}'.",
+                        percent_decode(&tab.request_data)
+                    ),
+                );
+                tab.reset_scroll = true;
+                tab.url_bar_data = decode_for_display(&tab.request_data);
+            } else {
+                tab.restoring = restoring;
+                tab.loading = Some(Loading::start(
+                    tab.server_name.clone(),
+                    tab.request_data.clone(),
+                    self.known_hosts.clone(),
+                    self.identities.get(&tab.server_name).cloned(),
+                    self.settings.request_options,
+                ));
+            }
+        }
+        if let Some(result) = tab.loading.as_ref().and_then(|loading| loading.poll()) {
+            tab.loading = None;
+            if !matches!(result, Ok((GeminiResponse::Redirection { .. }, _))) {
+                tab.redirects.clear();
+            }
+            let redirect_origin = tab.redirect_origin.take();
+            let restoring = std::mem::take(&mut tab.restoring);
+            match result {
+                Ok((response, raw)) => {
+                    tab.status = ResponseHeader::from_bytes(&raw);
+                    // Redirections leave the current page on screen
+                    let raw =
+                        (!matches!(response, GeminiResponse::Redirection { .. })).then_some(raw);
+                    match response {
+                        GeminiResponse::Success { meta, body, .. } => {
+                            tab.lang = meta.lang.clone();
+                            let page = match body {
+                                // Reloading an unchanged page keeps its entries, and their layout
+                                SuccessBody::Text(body)
+                                    if meta.mime == "text/gemini"
+                                        && matches!(tab.page, Page::Gemtext(_))
+                                        && !body.is_empty()
+                                        && body == tab.gemtext_source =>
+                                {
+                                    None
+                                }
+                                SuccessBody::Text(body) if meta.mime == "text/gemini" => {
+                                    let gemtext = Gemtext::from_str(&body)
+                                        .expect("[ERROR] Data received is not valid Gemtext.");
+                                    tab.gemtext_source = body;
+                                    Some(Page::Gemtext(gemtext))
+                                }
+                                SuccessBody::Text(body) => Some(Page::Text(body)),
+                                SuccessBody::Binary(data) if meta.mime.starts_with("image/") => {
+                                    Some(Page::Image {
+                                        texture: load_image(ctx, &data),
+                                        mime: meta.mime,
+                                        data,
+                                    })
+                                }
+                                SuccessBody::Binary(data) => Some(Page::Binary {
+                                    mime: meta.mime,
+                                    data,
+                                }),
+                            };
+                            if let Some(page) = page {
+                                tab.set_page(page);
+                            }
+                            tab.reset_scroll = true;
+                            tab.scroll_fragment = tab
+                                .request_data
+                                .split_once('#')
+                                .map(|(_, fragment)| percent_decode(fragment));
+                            if tab.scroll_fragment.is_some() {
+                                // The heading can only be scrolled to after the scroll reset
+                                ctx.request_repaint();
+                            }
+                            tab.push_history(redirect_origin.unwrap_or_else(|| {
+                                (tab.server_name.clone(), tab.request_data.clone())
+                            }));
+                            self.mark_visited(tab.current_url(), &tab.page);
+                            // Favicons are requested over Gemini
+                            if Protocol::from_url(&tab.request_data) == Protocol::Gemini {
+                                self.fetch_favicon(&tab.server_name, ctx);
+                            }
+                            self.refresh_stats = true;
+                            let url = cache_key(tab.current_url());
+                            // Uploads aren't pages, and a cached copy is already in the cache
+                            if tab.cached_at.is_none() && !url.starts_with("titan://") {
+                                if let Some(raw) = &raw {
+                                    if let Err(e) = self.cache.put(url, raw) {
+                                        remilog!("[CACHE ERROR] Couldn't cache '{url}': {e}");
+                                    }
+                                }
+                            }
+                        }
+                        GeminiResponse::PermanentFailure {
+                            kind: PermanentFailureKind::NotFound,
+                            msg,
+                            ..
+                        } => {
+                            remilog!(
+                                "[PERM::NOTFOUND] '{:?}' couldn't be found: '{msg}'",
+                                tab.request_data
+                            );
+                            tab.show_not_found(&msg);
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::PermanentFailure {
+                            kind: PermanentFailureKind::Gone,
+                            msg,
+                            ..
+                        } => {
+                            remilog!("[PERM::GONE] '{:?}' is gone: '{msg}'", tab.request_data);
+                            let mut details = format!(
+                                "'{}' was removed and won't come back.",
+                                percent_decode(&tab.request_data)
+                            );
+                            if !msg.trim().is_empty() {
+                                details.push_str(&format!("\nThe server said: {}", msg.trim()));
+                            }
+                            tab.show_error(
+                                "This resource is permanently gone".to_string(),
+                                details,
+                            );
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::PermanentFailure { code, kind, msg } => {
+                            remilog!(
+                                "[PERM::{kind:?}] Error {code} from server: '{msg}' with request: '{}'",
+                                tab.request_data
+                            );
+                            tab.show_error(format!("Permanent failure ({kind:?})"), msg);
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::TemporaryFailure {
+                            kind: TemporaryFailureKind::SlowDown,
+                            msg,
+                            ..
+                        } => {
+                            let delay = msg
+                                .trim()
+                                .parse()
+                                .unwrap_or(DEFAULT_SLOW_DOWN_DELAY)
+                                .min(MAX_SLOW_DOWN_DELAY);
+                            remilog!(
+                                "[TEMP::SlowDown] Retrying request '{}' in {delay} seconds",
+                                tab.request_data
+                            );
+                            tab.show_error(
+                                "Slow down".to_string(),
+                                format!("The server asked to wait before trying again, '{}' will be requested in {delay} seconds.", tab.request_data),
+                            );
+                            tab.retry_at = Some(Instant::now() + Duration::from_secs(delay));
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::TemporaryFailure { code, kind, msg } => {
+                            remilog!(
+                                "[TEMP::{kind:?}] Error {code} from server: '{msg}' with request: '{}'",
+                                tab.request_data
+                            );
+                            tab.show_error(format!("Temporary failure ({kind:?})"), msg);
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::ClientCertificate { code, kind, msg } => {
+                            remilog!(
+                                "[CERT::{kind:?}] Error {code} from server: '{msg}' with request: '{}'",
+                                tab.request_data
+                            );
+                            tab.set_page(Page::ClientCertificate {
+                                title: format!("Client certificate ({kind:?})"),
+                                msg,
+                            });
+                            tab.moving_in_history = false;
+                            tab.reset_scroll = true;
+                        }
+                        GeminiResponse::Redirection { kind, to, .. } => {
+                            tab.redirects.push(tab.request_data.clone());
+                            // Temporary targets must not replace the URL that was asked for
+                            tab.redirect_origin = redirect_origin.or_else(|| {
+                                matches!(kind, RedirectionKind::Temporary)
+                                    .then(|| (tab.server_name.clone(), tab.request_data.clone()))
+                            });
+                            if tab.redirects.len() > MAX_REDIRECTS {
+                                let mut chain = tab.redirects.join(" -> ");
+                                chain.push_str(" -> ");
+                                chain.push_str(&to);
+                                tab.redirects.clear();
+                                tab.redirect_origin = None;
+                                tab.show_error("Too many redirections".to_string(), chain);
+                                tab.reset_scroll = true;
+                            } else {
+                                let mut server_name = tab.server_name.clone();
+                                // Relative redirections after an upload point to the page, not to another upload
+                                let mut request_data =
+                                    match tab.request_data.strip_prefix("titan://") {
+                                        Some(rest) => format!("gemini://{rest}"),
+                                        None => tab.request_data.clone(),
+                                    };
+                                if !redirect(&mut server_name, &mut request_data, &to) {
+                                    tab.redirects.clear();
+                                    tab.redirect_origin = None;
+                                    tab.show_error("Invalid redirection".to_string(), to);
+                                    tab.reset_scroll = true;
+                                } else if !split_host_port(&server_name)
+                                    .0
+                                    .eq_ignore_ascii_case(split_host_port(&tab.server_name).0)
+                                {
+                                    tab.pending_redirect = Some(PendingRedirect {
+                                        from: tab.server_name.clone(),
+                                        server_name,
+                                        request_data,
+                                    });
+                                } else {
+                                    tab.server_name = server_name;
+                                    tab.request_data = request_data;
+                                    tab.redir = true;
+                                }
+                            }
+                        }
+                        GeminiResponse::Input { kind, prompt, .. } => {
+                            tab.pending_input = Some(PendingInput {
+                                kind,
+                                prompt,
+                                url: tab.request_data.clone(),
+                                text: String::new(),
+                            });
+                            tab.reset_scroll = true;
+                            tab.moving_in_history = false;
+                        }
+                    }
+                    if let Some(raw) = raw {
+                        tab.raw_response = raw;
+                    }
+                }
+                // A page of the last session that can't be loaded anymore is replaced by the home page.
+                // Certificate mismatches still have to be confirmed.
+                Err(e) if restoring && !matches!(e, RequestError::Tls(_)) => {
+                    remilog!(
+                        "[SESSION] Couldn't restore '{}', opening the home page instead: {e}",
+                        tab.request_data
+                    );
+                    tab.moving_in_history = false;
+                    tab.navigate(&self.settings.home_url);
+                    if !tab.redir {
+                        tab.navigate(DEFAULT_URL);
+                    }
+                }
+                Err(e) => {
+                    tab.status = None;
+                    remilog!(
+                        "[REQUEST ERROR] Request error from server '{}' with request '{}': {e}",
+                        tab.server_name,
+                        tab.request_data
+                    );
+                    let parse_error = match &e {
+                        RequestError::Protocol(e) => e.downcast_ref::<GeminiResponseParseError>(),
+                        _ => None,
+                    };
+                    // Keep the request around so it can be retried if the new certificate is trusted.
+                    // Other tabs may be loading, only a mismatch for this tab's host is taken.
+                    let host = split_host_port(&tab.server_name).0;
+                    tab.cert_mismatch = self
+                        .known_hosts
+                        .lock()
+                        .unwrap()
+                        .mismatch
+                        .take_if(|m| m.host == host);
+                    let network_error = tab.cert_mismatch.is_none() && parse_error.is_none();
+                    tab.offline_copy = network_error
+                        .then(|| self.cache.get(cache_key(&tab.request_data)))
+                        .flatten();
+                    if let Some(e) = parse_error {
+                        tab.show_error(
+                            "Malformed response".to_string(),
+                            format!(
+                                "'{}' didn't answer with a valid Gemini response, {}. {} bytes were received.",
+                                tab.server_name, e.reason, e.len
+                            ),
+                        );
+                        if let Page::Error { detail, .. } = &mut tab.page {
+                            *detail = Some(if e.head.is_empty() {
+                                "Nothing was received.".to_string()
+                            } else {
+                                response::hexdump(&e.head)
+                            });
+                        }
+                        tab.reset_scroll = true;
+                    } else if tab.cert_mismatch.is_none() {
+                        let server_name = &tab.server_name;
+                        let (title, msg) = match &e {
+                            RequestError::Dns(_) => (
+                                "Couldn't resolve host",
+                                format!("'{host}' couldn't be found, check the address and your connection."),
+                            ),
+                            RequestError::Connect(_) => (
+                                "Couldn't connect",
+                                format!("'{server_name}' couldn't be reached."),
+                            ),
+                            RequestError::Tls(_) => (
+                                "TLS handshake failed",
+                                format!("A secure connection to '{server_name}' couldn't be established."),
+                            ),
+                            RequestError::Timeout => (
+                                "Request timed out",
+                                format!("'{server_name}' didn't respond in time."),
+                            ),
+                            RequestError::Protocol(_) => (
+                                "Request failed",
+                                format!("'{}' couldn't be loaded.", percent_decode(&tab.request_data)),
+                            ),
+                            RequestError::TooLarge(_) => (
+                                "Too large",
+                                format!("'{}' couldn't be loaded.", percent_decode(&tab.request_data)),
+                            ),
+                        };
+                        tab.show_error(title.to_string(), msg);
+                        if let Page::Error { detail, .. } = &mut tab.page {
+                            *detail = Some(e.to_string());
+                        }
+                        tab.reset_scroll = true;
+                    }
+                }
+            }
+            tab.url_bar_data = decode_for_display(&tab.request_data);
+        }
+        if tab.loading.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        if let Some(check) = &mut tab.link_check {
+            let report = check.poll().then(|| check.to_gemtext());
+            if !check.done() {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+            if let Some(report) = report {
+                tab.set_page(Page::Gemtext(report));
+            }
+        }
+    }
+
+    /// Replaces the page of `tab` with a report on its links, which are checked in the background
+    fn check_links(&self, tab: &mut Tab) {
+        let Page::Gemtext(gemtext) = &tab.page else {
+            return;
+        };
+        let links: Vec<_> = gemtext
+            .links()
+            .map(|(url, _)| resolve_url(&tab.request_data, url))
+            .collect();
+        remilog!(
+            "[LINKCHECK] Checking {} links of '{}'",
+            links.len(),
+            tab.current_url()
+        );
+        let check = LinkCheck::start(
+            tab.current_url(),
+            links,
+            &self.known_hosts,
+            &self.identities,
+            self.settings.request_options,
+        );
+        tab.set_page(Page::Gemtext(check.to_gemtext()));
+        tab.link_check = Some(check);
+        tab.gemtext_source.clear();
+        tab.raw_response.clear();
+        tab.reset_scroll = true;
+    }
+
+    /// Updates the subscriptions after `page` was loaded from `url`.
+    /// Visiting a subscribed page marks all of its entries as seen.
+    fn mark_visited(&mut self, url: &str, page: &Page) {
+        for subscription in &mut self.subscriptions {
+            if subscription.url != url {
+                subscription.mark_seen(url);
+            } else if let Page::Gemtext(gemtext) = page {
+                subscription.update(gemtext);
+                subscription.mark_all_seen();
+            }
+        }
+    }
+
+    /// Fetches every subscribed page in the background to look for new entries
+    fn check_subscriptions(&mut self) {
+        if self.feed_updates.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        for subscription in &self.subscriptions {
+            let Ok(url) = GeminiUrl::parse(&subscription.url) else {
+                continue;
+            };
+            let protocol = Protocol::from_url(&subscription.url);
+            // Like pages, Spartan feeds are only fetched while Spartan support is on
+            if protocol == Protocol::Spartan && !self.settings.request_options.spartan {
+                continue;
+            }
+            let server_name = url.authority();
+            let request_data = subscription.url.clone();
+            let known_hosts = self.known_hosts.clone();
+            let identity = self.identities.get(&server_name).cloned();
+            let options = self.settings.request_options;
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let res = protocol.request(
+                    &server_name,
+                    &request_data,
+                    &known_hosts,
+                    identity.as_ref(),
+                    options,
+                    &CancelHandle::default(),
+                );
+                forget_mismatch(&known_hosts, &server_name);
+                let _ = sender.send((request_data, res));
+            });
+        }
+        self.feed_updates = Some(receiver);
+    }
+
+    /// Handles the responses of `check_subscriptions` that arrived, and refreshes the pages listing them
+    fn poll_subscriptions(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.feed_updates else {
+            return;
+        };
+        let mut results = vec![];
+        let done = loop {
+            match receiver.try_recv() {
+                Ok(result) => results.push(result),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        for (url, result) in &results {
+            match result {
+                Ok((
+                    GeminiResponse::Success {
+                        meta,
+                        body: SuccessBody::Text(body),
+                        ..
+                    },
+                    _,
+                )) if meta.mime == "text/gemini" => {
+                    let subscription = self.subscriptions.iter_mut().find(|s| &s.url == url);
+                    if let (Some(subscription), Ok(gemtext)) =
+                        (subscription, Gemtext::from_str(body))
+                    {
+                        subscription.update(&gemtext);
+                    }
+                }
+                Ok((_, raw)) => remilog!(
+                    "[FEED ERROR] '{url}' isn't a Gemtext page, the server answered '{}'",
+                    ResponseHeader::from_bytes(raw)
+                        .map(|h| format!("{} {}", h.code, h.meta))
+                        .unwrap_or_default()
+                ),
+                Err(e) => remilog!("[FEED ERROR] Couldn't check '{url}': {e}"),
+            }
+        }
+        if done {
+            self.feed_updates = None;
+        } else {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if done || !results.is_empty() {
+            for tab in &mut self.tabs {
+                if tab.request_data == "about:subscriptions" {
+                    tab.set_page(subscriptions_page(&self.subscriptions, !done));
+                }
+            }
+        }
+    }
+
+    /// Requests `/favicon.txt` from `server_name` in the background, unless it was already asked for it
+    fn fetch_favicon(&mut self, server_name: &str, ctx: &egui::Context) {
+        if self.favicons.contains_key(server_name) {
+            return;
+        }
+        self.favicons.insert(server_name.to_string(), None);
+        let server_name = server_name.to_string();
+        let known_hosts = self.known_hosts.clone();
+        let identity = self.identities.get(&server_name).cloned();
+        let options = RequestOptions {
+            max_response_size: MAX_FAVICON_RESPONSE_SIZE,
+            ..self.settings.request_options
+        };
+        let sender = self.favicon_sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let request_data = format!("gemini://{server_name}/favicon.txt");
+            let res = request(
+                &server_name,
+                request_data.as_bytes(),
+                &known_hosts,
+                identity.as_ref(),
+                options,
+                &CancelHandle::default(),
+            );
+            forget_mismatch(&known_hosts, &server_name);
+            // Hosts without a favicon are common, failures aren't worth logging
+            let favicon = match res {
+                Ok((
+                    GeminiResponse::Success {
+                        body: SuccessBody::Text(body),
+                        ..
+                    },
+                    _,
+                )) => parse_favicon(&body),
+                _ => None,
+            };
+            let _ = sender.send((server_name, favicon));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Stores the favicons that arrived
+    fn poll_favicons(&mut self) {
+        while let Ok((server_name, favicon)) = self.favicon_receiver.try_recv() {
+            self.favicons.insert(server_name, favicon);
+        }
+    }
+
+    /// Emoji of the host of the page shown in `tab`, internal pages have none
+    fn favicon(&self, tab: &Tab) -> Option<&str> {
+        if about::page_name(tab.current_url()).is_some() {
+            return None;
+        }
+        self.favicons.get(&tab.server_name)?.as_deref()
+    }
+
+    /// Shows the tab strip, returns the index of the tab to close if one was closed
+    fn tab_strip(&mut self, ui: &mut egui::Ui) -> Option<usize> {
+        let theme = self.settings.theme;
+        let mut close = None;
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (i, tab) in self.tabs.iter().enumerate() {
+                    let response = ui
+                        .selectable_label(
+                            i == self.active_tab,
+                            RichText::new(match self.favicon(tab) {
+                                Some(favicon) => format!("{favicon} {}", tab.title()),
+                                None => tab.title(),
+                            })
+                            .size(TEXT_SIZE / 1.25)
+                            .color(theme.text),
+                        )
+                        .on_hover_text(tab.current_url());
+                    if response.clicked() {
+                        self.active_tab = i;
+                    }
+                    if response.middle_clicked()
+                        || ui
+                            .small_button(RichText::new("x").color(theme.text))
+                            .on_hover_text("Close tab (Ctrl+W)")
+                            .clicked()
+                    {
+                        close = Some(i);
+                    }
+                }
+                if ui
+                    .button(RichText::new("+").size(TEXT_SIZE / 1.25).color(theme.text))
+                    .on_hover_text("New tab (Ctrl+T)")
+                    .clicked()
+                {
+                    self.new_tab();
+                }
+            });
+        });
+        close
+    }
+
+    /// Builds the internal page shown for `about:<name>` in `tab`, the index of the pages if it doesn't exist
+    fn about_page(&self, name: &str, tab: &Tab) -> Page {
+        let Some(page) = AboutPage::from_name(name) else {
+            return Page::Gemtext(about::index(Some(name)));
+        };
+        match page {
+            AboutPage::Bookmarks => {
+                let mut gemtext = bookmarks::to_gemtext(&self.bookmarks);
+                if self.bookmarks.is_empty() {
+                    gemtext.data.push(GemtextEntry::Text(
+                        "No bookmarks yet, use '+' to bookmark the current page.".to_string(),
+                    ));
+                }
+                Page::Gemtext(gemtext)
+            }
+            AboutPage::History => history_page(&tab.history, tab.loaded_history_index),
+            AboutPage::Subscriptions => {
+                subscriptions_page(&self.subscriptions, self.feed_updates.is_some())
+            }
+            // Filled in by `update` once the history of every tab is available
+            AboutPage::Stats => stats_page(&[]),
+            AboutPage::Settings => Page::Settings,
+            AboutPage::Index => Page::Gemtext(about::index(None)),
+        }
+    }
+
+    /// Loads what is in the URL bar, which is sent to the search engine when it isn't a URL.
+    /// The bar is reverted to the current URL if it can't be loaded.
+    fn submit_url_bar(&self, tab: &mut Tab) {
+        let url = if is_search_query(&tab.url_bar_data) {
+            let search_url = self
+                .settings
+                .search_url
+                .split('?')
+                .next()
+                .unwrap_or_default();
+            format!("{search_url}?{}", percent_encode(tab.url_bar_data.trim()))
+        } else {
+            tab.url_bar_data.clone()
+        };
+        tab.navigate(&url);
+        if !tab.redir {
+            tab.url_bar_data = decode_for_display(&tab.request_data);
+        }
+    }
+
+    /// Controls of the `about:settings` page, changes apply right away
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        let theme = self.settings.theme;
+        let label = |text: &str| RichText::new(text).size(TEXT_SIZE).color(theme.text);
+        let heading = |text: &str| RichText::new(text).size(MEDIUM_SIZE).color(theme.heading);
+        ui.label(heading("Settings"));
+
+        ui.label(
+            RichText::new("General")
+                .size(MINOR_SIZE)
+                .color(theme.heading),
+        );
+        ui.label(label("Home URL"));
+        ui.add(
+            egui::TextEdit::singleline(&mut self.settings.home_url)
+                .font(egui::FontId::proportional(TEXT_SIZE))
+                .text_color(theme.bright_text),
+        );
+        ui.label(label("Search engine"));
+        ui.add(
+            egui::TextEdit::singleline(&mut self.settings.search_url)
+                .font(egui::FontId::proportional(TEXT_SIZE))
+                .text_color(theme.bright_text),
+        )
+        .on_hover_text("What is typed in the URL bar is sent as its query when it isn't a URL");
+        ui.checkbox(
+            &mut self.settings.restore_session,
+            label("Restore tabs on startup"),
+        )
+        .on_hover_text("Reopen the tabs of the last session instead of the home page");
+
+        ui.label(
+            RichText::new("Appearance")
+                .size(MINOR_SIZE)
+                .color(theme.heading),
+        );
+        ui.label(label("Theme"));
+        ui.horizontal(|ui| {
+            let mut themes = vec![("Dark", Theme::DARK), ("Light", Theme::LIGHT)];
+            if let Some(custom_theme) = self.settings.custom_theme {
+                themes.push(("Custom", custom_theme));
+            }
+            for (name, new_theme) in themes {
+                if ui
+                    .radio(self.settings.theme == new_theme, label(name))
+                    .clicked()
+                {
+                    self.settings.theme = new_theme;
+                    ui.ctx().request_repaint();
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(label("Zoom"));
+            ui.add(
+                egui::Slider::new(&mut self.settings.zoom, MIN_ZOOM..=MAX_ZOOM)
+                    .step_by(ZOOM_STEP.into()),
+            )
+            .on_hover_text("Ctrl+Plus and Ctrl+Minus, Ctrl+0 resets it");
+        });
+        ui.checkbox(
+            &mut self.settings.collapse_blank_lines,
+            label("Collapse blank lines"),
+        )
+        .on_hover_text("Show runs of empty lines as a single one");
+        ui.checkbox(&mut self.settings.linkify_text, label("Link URLs in text"))
+            .on_hover_text("Make gemini:// and http(s):// URLs written in text clickable");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.reader_mode, label("Reader mode"))
+                .on_hover_text("Show the page in a narrow centered column");
+            ui.add_enabled(
+                self.settings.reader_mode,
+                egui::DragValue::new(&mut self.settings.reader_width)
+                    .range(MIN_READER_WIDTH..=MAX_READER_WIDTH)
+                    .suffix(" characters"),
+            );
+        });
+
+        ui.label(
+            RichText::new("Network")
+                .size(MINOR_SIZE)
+                .color(theme.heading),
+        );
+        let options = &mut self.settings.request_options;
+        for (name, timeout) in [
+            ("Connect timeout", &mut options.connect_timeout),
+            ("Read timeout", &mut options.read_timeout),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(label(name));
+                let mut secs = timeout.as_secs();
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut secs)
+                            .range(1..=MAX_TIMEOUT)
+                            .suffix(" s"),
+                    )
+                    .changed()
+                {
+                    *timeout = Duration::from_secs(secs);
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label(label("Default port"));
+            ui.add(egui::DragValue::new(&mut options.default_port).range(1..=u16::MAX))
+                .on_hover_text("Used when the URL doesn't include a port, 1965 for Gemini");
+        });
+        ui.checkbox(&mut options.spartan, label("Spartan support"))
+            .on_hover_text("Open spartan:// links, Spartan is a Gemini sibling without TLS");
+        ui.horizontal(|ui| {
+            ui.label(label("Offline cache (MiB)"));
+            let mut mib = self.settings.cache_size / (1024 * 1024);
+            if ui
+                .add(egui::DragValue::new(&mut mib).range(0..=10_000))
+                .on_hover_text("Visited pages are kept to be shown when they can't be loaded")
+                .changed()
+            {
+                self.settings.cache_size = mib * 1024 * 1024;
+                self.cache.max_size = self.settings.cache_size;
+                self.cache.evict();
+            }
+        });
+
+        ui.label(
+            RichText::new("Certificates")
+                .size(MINOR_SIZE)
+                .color(theme.heading),
+        );
+        let options = &mut self.settings.request_options;
+        for mode in VerificationMode::ALL {
+            ui.radio_value(&mut options.verification, mode, label(mode.label()));
+        }
+        match options.verification {
+            VerificationMode::TrustAll => {
+                ui.label(
+                    RichText::new("Any certificate is accepted, connections can be intercepted")
+                        .size(TEXT_SIZE)
+                        .color(theme.error),
+                );
+            }
+            VerificationMode::Strict => {
+                ui.label(label("Self-signed certificates are rejected"));
+            }
+            VerificationMode::Tofu => {}
+        }
+    }
+
+    /// Shows the bookmark at `i` in the bookmarks menu.
+    /// `prev` and `next` are the bookmarks it is swapped with when moved up or down.
+    fn bookmark_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        i: usize,
+        prev: Option<usize>,
+        next: Option<usize>,
+        folders: &[String],
+    ) -> Option<BookmarkAction> {
+        let theme = self.settings.theme;
+        let mut action = None;
+        if !self.editing_bookmarks {
+            let bookmark = &self.bookmarks[i];
+            let response = ui
+                .button(
+                    RichText::new(bookmark.name())
+                        .size(TEXT_SIZE)
+                        .color(theme.text),
+                )
+                .on_hover_text(&bookmark.url);
+            if response.clicked() {
+                action = Some(BookmarkAction::Open(i));
+            }
+            if response.secondary_clicked() {
+                action = Some(BookmarkAction::Remove(i));
+            }
+            return action;
+        }
+        ui.horizontal(|ui| {
+            let button =
+                |text| egui::Button::new(RichText::new(text).size(TEXT_SIZE).color(theme.text));
+            if let Some(prev) = prev {
+                if ui.add(button("^")).clicked() {
+                    action = Some(BookmarkAction::Swap(prev, i));
+                }
+            }
+            if let Some(next) = next {
+                if ui.add(button("v")).clicked() {
+                    action = Some(BookmarkAction::Swap(i, next));
+                }
+            }
+            if ui
+                .button(RichText::new("x").size(TEXT_SIZE).color(theme.error))
+                .clicked()
+            {
+                action = Some(BookmarkAction::Remove(i));
+            }
+            ui.menu_button(
+                RichText::new("Folder").size(TEXT_SIZE).color(theme.text),
+                |ui| {
+                    if ui
+                        .button(RichText::new("No folder").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        action = Some(BookmarkAction::Move(i, None));
+                        ui.close_menu();
+                    }
+                    for folder in folders {
+                        if ui
+                            .button(RichText::new(folder).size(TEXT_SIZE).color(theme.text))
+                            .clicked()
+                        {
+                            action = Some(BookmarkAction::Move(i, Some(folder.clone())));
+                            ui.close_menu();
+                        }
+                    }
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.new_folder_name)
+                            .font(egui::FontId::proportional(TEXT_SIZE))
+                            .text_color(theme.bright_text)
+                            .hint_text("New folder"),
+                    );
+                    if response.lost_focus()
+                        && ui.input(|i| i.key_pressed(Key::Enter))
+                        && !self.new_folder_name.trim().is_empty()
+                    {
+                        let folder = self.new_folder_name.trim().to_string();
+                        action = Some(BookmarkAction::Move(i, Some(folder)));
+                        ui.close_menu();
+                    }
+                },
+            );
+            let bookmark = &mut self.bookmarks[i];
+            ui.add(
+                egui::TextEdit::singleline(&mut bookmark.title)
+                    .font(egui::FontId::proportional(TEXT_SIZE))
+                    .text_color(theme.bright_text)
+                    .hint_text(&bookmark.url),
+            );
+        });
+        action
+    }
+
+    /// Downloads the target of the link `url` to a file picked by the user, without showing it
+    fn save_link_target(&self, tab: &Tab, url: &str) {
+        let mut server_name = tab.server_name.clone();
+        let mut request_data = tab.request_data.clone();
+        // Downloads are only implemented for Gemini
+        if !redirect(&mut server_name, &mut request_data, url)
+            || about::page_name(&request_data).is_some()
+            || Protocol::from_url(&request_data) == Protocol::Spartan
+        {
+            remilog!("[DOWNLOAD ERROR] '{url}' can't be downloaded.");
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name(file_name(&request_data))
+            .save_file()
+        else {
+            return;
+        };
+        let known_hosts = self.known_hosts.clone();
+        let identity = self.identities.get(&server_name).cloned();
+        let options = self.settings.request_options;
+        std::thread::spawn(move || {
+            remilog!(
+                "[DOWNLOAD] Saving '{request_data}' to '{}'.",
+                path.display()
+            );
+            let res = download(
+                &server_name,
+                request_data.as_bytes(),
+                &known_hosts,
+                identity.as_ref(),
+                options,
+                &CancelHandle::default(),
+                &path,
+            );
+            forget_mismatch(&known_hosts, &server_name);
+            match res {
+                Ok(written) => remilog!(
+                    "[DOWNLOAD] Saved {written} bytes from '{request_data}' to '{}'.",
+                    path.display()
+                ),
+                Err(e) => remilog!("[DOWNLOAD ERROR] Couldn't download '{request_data}': {e}"),
+            }
+        });
+    }
+}
+
+impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(BOOKMARKS_STORE_KEY, bookmarks::to_lines(&self.bookmarks));
+        storage.set_string(
+            SUBSCRIPTIONS_STORE_KEY,
+            subscriptions::to_lines(&self.subscriptions),
+        );
+        storage.set_string(
+            KNOWN_HOSTS_STORE_KEY,
+            self.known_hosts.lock().unwrap().to_lines(),
+        );
+        // Settings overridden by flags keep their stored value
+        let mut settings = self.settings.clone();
+        settings.home_url = saved_setting(&self.settings.home_url, &self.home_url_override).clone();
+        settings.request_options.verification = *saved_setting(
+            &self.settings.request_options.verification,
+            &self.verification_override,
+        );
+        settings.save(storage);
+        storage.set_string(TABS_STORE_KEY, tabs_to_lines(&self.tabs, self.active_tab));
+    }
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let theme = self.settings.theme;
+        ctx.style_mut(|style| style.visuals = theme.visuals());
+
+        let mut close = None;
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| close = self.tab_strip(ui));
+        ctx.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::COMMAND, Key::T) {
+                self.new_tab();
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, Key::W) {
+                close = Some(self.active_tab);
+            }
+            let len = self.tabs.len();
+            if i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, Key::Tab) {
+                self.active_tab = (self.active_tab + len - 1) % len;
+            } else if i.consume_key(egui::Modifiers::COMMAND, Key::Tab) {
+                self.active_tab = (self.active_tab + 1) % len;
+            }
+        });
+        if let Some(i) = close {
+            self.close_tab(i);
+        }
+
+        self.poll_subscriptions(ctx);
+        self.poll_favicons();
+        // Background tabs keep loading
+        let mut tabs = std::mem::take(&mut self.tabs);
+        for tab in &mut tabs {
+            self.update_loading(tab, ctx);
+        }
+        self.tabs = tabs;
+        if std::mem::take(&mut self.refresh_stats) {
+            let visits = host_visits(&self.tabs);
+            for tab in &mut self.tabs {
+                if tab.request_data == "about:stats" {
+                    tab.set_page(stats_page(&visits));
+                }
+            }
+        }
+
+        // The active tab is taken out for the rest of the frame, so that it can be borrowed along with the app
+        let mut tab = self.tabs.remove(self.active_tab);
+        let reset_scroll = std::mem::take(&mut tab.reset_scroll);
+        // Consumed before the page is drawn so that it never reaches a focused label or text field
+        let focus_url_bar = ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, Key::L));
+        // Ctrl+C copies the URL unless there's text to copy
+        let copy_url = !ctx.wants_keyboard_input()
+            && !egui::text_selection::LabelSelectionState::load(ctx).has_selection()
+            && ctx.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Copy)));
+        if copy_url {
+            ctx.copy_text(tab.current_url().clone());
+        }
+        // Ctrl+Shift+V loads the URL in the clipboard, unless it's pasted in a text field
+        let pasted_url = if ctx.wants_keyboard_input() {
+            None
+        } else {
+            ctx.input_mut(|i| {
+                if !(i.modifiers.command && i.modifiers.shift) {
+                    return None;
+                }
+                let index = i
+                    .events
+                    .iter()
+                    .position(|e| matches!(e, egui::Event::Paste(_)))?;
+                match i.events.remove(index) {
+                    egui::Event::Paste(text) => Some(text),
+                    _ => None,
+                }
+            })
+        };
+        if let Some(text) = pasted_url {
+            tab.url_bar_data = text.trim().to_string();
+            self.submit_url_bar(&mut tab);
+        }
+        egui::TopBottomPanel::top("url_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(RichText::new("<").size(TEXT_SIZE).color(theme.text))
+                    .on_hover_text("Back (Alt+Left)")
+                    .clicked()
+                {
+                    tab.go_back();
+                }
+
+                if ui
+                    .button(RichText::new(">").size(TEXT_SIZE).color(theme.text))
+                    .on_hover_text("Forward (Alt+Right)")
+                    .clicked()
+                {
+                    tab.go_forward();
+                }
+
+                if ui
+                    .add_enabled(
+                        tab.parent_url().is_some(),
+                        egui::Button::new(RichText::new("^").size(TEXT_SIZE).color(theme.text)),
+                    )
+                    .on_hover_text("Up one level (Alt+Up)")
+                    .clicked()
+                {
+                    tab.go_up();
+                }
+
+                let home_button_response = ui
+                    .button(RichText::new("~").size(TEXT_SIZE).color(theme.text))
+                    .on_hover_text("Home");
+                if home_button_response.clicked() {
+                    tab.navigate(&self.settings.home_url);
+                }
+                home_button_response.context_menu(|ui| {
+                    if ui
+                        .button(
+                            RichText::new("Set current page as home")
+                                .size(TEXT_SIZE)
+                                .color(theme.text),
+                        )
+                        .clicked()
+                    {
+                        self.settings.home_url = tab.current_url().clone();
+                        ui.close_menu();
+                    }
+                });
+
+                if tab.loading.is_some() {
+                    if ui
+                        .button(RichText::new("x").size(TEXT_SIZE).color(theme.text))
+                        .on_hover_text("Stop")
+                        .clicked()
+                    {
+                        tab.stop();
+                    } else {
+                        ui.spinner();
+                    }
+                }
+                ui.menu_button(RichText::new("#").size(TEXT_SIZE).color(theme.text), |ui| {
+                    ui.set_max_width(ui.min_size().x);
+                    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+                    ui.style_mut().visuals.widgets.inactive.weak_bg_fill = theme.bg;
+                    ui.style_mut().visuals.widgets.hovered.weak_bg_fill = theme.hovered_bg;
+
+                    if ui
+                        .button(
+                            RichText::new("All bookmarks")
+                                .size(TEXT_SIZE)
+                                .color(theme.bright_text),
+                        )
+                        .clicked()
+                    {
+                        tab.navigate("about:bookmarks");
+                    }
+                    if ui
+                        .button(
+                            RichText::new("Subscriptions")
+                                .size(TEXT_SIZE)
+                                .color(theme.bright_text),
+                        )
+                        .clicked()
+                    {
+                        tab.navigate("about:subscriptions");
+                    }
+                    if ui
+                        .button(
+                            RichText::new("Most visited")
+                                .size(TEXT_SIZE)
+                                .color(theme.bright_text),
+                        )
+                        .clicked()
+                    {
+                        tab.navigate("about:stats");
+                    }
+                    let url = tab.current_url();
+                    let subscribed = self.subscriptions.iter().position(|s| &s.url == url);
+                    if let Some(i) = subscribed {
+                        if ui
+                            .button(
+                                RichText::new("Unsubscribe from this page")
+                                    .size(TEXT_SIZE)
+                                    .color(theme.text),
+                            )
+                            .clicked()
+                        {
+                            self.subscriptions.remove(i);
+                        }
+                    } else if let Page::Gemtext(gemtext) = &tab.page {
+                        if about::page_name(url).is_none()
+                            && ui
+                                .button(
+                                    RichText::new("Subscribe to this page")
+                                        .size(TEXT_SIZE)
+                                        .color(theme.text),
+                                )
+                                .clicked()
+                        {
+                            let mut subscription =
+                                Subscription::new(url, tab.page_title().unwrap_or_default());
+                            // Only entries published from now on are new
+                            subscription.update(gemtext);
+                            subscription.mark_all_seen();
+                            self.subscriptions.push(subscription);
+                        }
+                    }
+                    ui.checkbox(
+                        &mut self.editing_bookmarks,
+                        RichText::new("Edit").size(TEXT_SIZE).color(theme.text),
+                    );
+                    let folders = bookmarks::folders(&self.bookmarks);
+                    let mut action = None;
+                    // Bookmarks without a folder come first, then one collapsible section per folder
+                    for folder in std::iter::once(None).chain(folders.iter().map(Some)) {
+                        let indices: Vec<usize> = (0..self.bookmarks.len())
+                            .filter(|&i| self.bookmarks[i].folder.as_ref() == folder)
+                            .collect();
+                        let mut show_rows = |ui: &mut egui::Ui| {
+                            for (n, &i) in indices.iter().enumerate() {
+                                let prev = n.checked_sub(1).map(|n| indices[n]);
+                                let next = indices.get(n + 1).copied();
+                                if let Some(a) = self.bookmark_row(ui, i, prev, next, &folders) {
+                                    action = Some(a);
+                                }
+                            }
+                        };
+                        match folder {
+                            None => show_rows(ui),
+                            Some(folder) => {
+                                egui::CollapsingHeader::new(
+                                    RichText::new(folder)
+                                        .size(TEXT_SIZE)
+                                        .color(theme.bright_text),
+                                )
+                                .show(ui, show_rows);
+                            }
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(RichText::new("Import...").size(TEXT_SIZE).color(theme.text))
+                            .clicked()
+                        {
+                            import_bookmarks(&mut self.bookmarks);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button(RichText::new("Export...").size(TEXT_SIZE).color(theme.text))
+                            .clicked()
+                        {
+                            export_bookmarks(&self.bookmarks);
+                            ui.close_menu();
+                        }
+                    });
+                    match action {
+                        Some(BookmarkAction::Open(i)) => {
+                            tab.navigate(&self.bookmarks[i].url);
+                        }
+                        Some(BookmarkAction::Remove(i)) => {
+                            self.bookmarks.remove(i);
+                        }
+                        Some(BookmarkAction::Swap(a, b)) => self.bookmarks.swap(a, b),
+                        Some(BookmarkAction::Move(i, folder)) => {
+                            self.bookmarks[i].folder = folder;
+                            self.new_folder_name.clear();
+                        }
+                        None => (),
+                    }
+                    ui.style_mut().wrap_mode = None;
+                });
+
+                let bookmark = tab.current_url();
+                if ui
+                    .button(RichText::new("+").size(TEXT_SIZE).color(theme.text))
+                    .clicked()
+                    && !self.bookmarks.iter().any(|b| &b.url == bookmark)
+                {
+                    self.bookmarks.push(Bookmark::new(
+                        bookmark,
+                        tab.page_title().unwrap_or_default(),
+                    ))
+                }
+
+                let available_width = ui.available_width();
+                let console_button_response =
+                    ui.menu_button(RichText::new("$").size(TEXT_SIZE).color(theme.text), |ui| {
+                        ui.set_max_width(available_width);
+                        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+                        ui.label(
+                            RichText::new("Console")
+                                .size(MINOR_SIZE)
+                                .color(theme.bright_text)
+                                .underline(),
+                        );
+                        ui.label(
+                            RichText::new(OUT.lock().unwrap().as_str())
+                                .size(TEXT_SIZE / 1.5)
+                                .color(theme.text)
+                                .monospace(),
+                        );
+                        ui.style_mut().wrap_mode = None;
+                    });
+                if console_button_response.response.secondary_clicked() {
+                    OUT.lock().unwrap().clear();
+                }
+
+                let has_headings = matches!(&tab.page, Page::Gemtext(gemtext)
+                    if gemtext.headings().next().is_some());
+                if has_headings {
+                    ui.toggle_value(
+                        &mut self.show_toc,
+                        RichText::new("¶").size(TEXT_SIZE).color(theme.text),
+                    )
+                    .on_hover_text("Table of contents");
+                }
+
+                ui.menu_button(RichText::new("=").size(TEXT_SIZE).color(theme.text), |ui| {
+                    if ui
+                        .button(RichText::new("Settings").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        tab.navigate("about:settings");
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button(
+                            RichText::new("Save page as...")
+                                .size(TEXT_SIZE)
+                                .color(theme.text),
+                        )
+                        .clicked()
+                    {
+                        tab.save_page();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button(RichText::new("Copy URL").size(TEXT_SIZE).color(theme.text))
+                        .on_hover_text("Ctrl+C when no text is selected")
+                        .clicked()
+                    {
+                        ui.ctx().copy_text(tab.current_url().clone());
+                        ui.close_menu();
+                    }
+                    if let Page::Gemtext(gemtext) = &tab.page {
+                        if ui
+                            .button(
+                                RichText::new("Copy as Gemtext")
+                                    .size(TEXT_SIZE)
+                                    .color(theme.text),
+                            )
+                            .clicked()
+                        {
+                            ui.ctx().copy_text(gemtext.to_string());
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button(
+                                RichText::new("Copy as Markdown")
+                                    .size(TEXT_SIZE)
+                                    .color(theme.text),
+                            )
+                            .clicked()
+                        {
+                            ui.ctx().copy_text(gemtext::to_markdown(gemtext));
+                            ui.close_menu();
+                        }
+                    }
+                    if matches!(tab.page, Page::Gemtext(_))
+                        && tab.link_check.is_none()
+                        && ui
+                            .button(
+                                RichText::new("Check links")
+                                    .size(TEXT_SIZE)
+                                    .color(theme.text),
+                            )
+                            .on_hover_text(
+                                "Reports the links of this page that don't answer with a success",
+                            )
+                            .clicked()
+                    {
+                        self.check_links(&mut tab);
+                        ui.close_menu();
+                    }
+                    ui.checkbox(
+                        &mut tab.view_source,
+                        RichText::new("View source")
+                            .size(TEXT_SIZE)
+                            .color(theme.text),
+                    )
+                    .on_hover_text("Ctrl+U");
+                    ui.checkbox(
+                        &mut self.wrap_text,
+                        RichText::new("Wrap text").size(TEXT_SIZE).color(theme.text),
+                    )
+                    .on_hover_text(
+                        "Scroll long lines horizontally like preformatted blocks when unchecked",
+                    );
+                });
+
+                ui.style_mut().override_font_id = Some(egui::FontId {
+                    size: TEXT_SIZE,
+                    family: egui::FontFamily::Proportional,
+                });
+                if self.settings.request_options.verification == VerificationMode::TrustAll {
+                    ui.label(RichText::new("Insecure").size(TEXT_SIZE).color(theme.error))
+                        .on_hover_text("Certificates aren't checked, change this in the settings");
+                }
+                if let Some(favicon) = self.favicon(&tab) {
+                    ui.label(RichText::new(favicon).size(TEXT_SIZE));
+                }
+                let text_edit = egui::TextEdit::singleline(&mut tab.url_bar_data)
+                    .text_color(theme.bright_text)
+                    .desired_width(f32::INFINITY);
+                let output = text_edit.show(ui);
+                if focus_url_bar {
+                    output.response.request_focus();
+                    let mut state = output.state;
+                    state
+                        .cursor
+                        .set_char_range(Some(egui::text::CCursorRange::two(
+                            egui::text::CCursor::new(0),
+                            egui::text::CCursor::new(tab.url_bar_data.chars().count()),
+                        )));
+                    state.store(ui.ctx(), output.response.id);
+                }
+                let lost_focus = output.response.lost_focus();
+                ui.style_mut().override_font_id = None;
+                if lost_focus && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    self.submit_url_bar(&mut tab);
+                } else if lost_focus
+                    && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, Key::Escape))
+                {
+                    // Escape already took the focus away, the edit is reverted.
+                    // It's consumed so that it doesn't also close the find bar.
+                    tab.url_bar_data = decode_for_display(&tab.request_data);
+                }
+            })
+        });
+        ctx.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::COMMAND, Key::Plus)
+                || i.consume_key(egui::Modifiers::COMMAND, Key::Equals)
+            {
+                self.settings.zoom += ZOOM_STEP;
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, Key::Minus) {
+                self.settings.zoom -= ZOOM_STEP;
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, Key::Num0) {
+                self.settings.zoom = 1.;
+            }
+        });
+        // Rounded so that repeated steps land back on 1
+        self.settings.zoom =
+            ((self.settings.zoom / ZOOM_STEP).round() * ZOOM_STEP).clamp(MIN_ZOOM, MAX_ZOOM);
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, Key::S)) {
+            tab.save_page();
+        }
+        // Keys without Ctrl belong to the text field that has the focus, if any
+        let keyboard = !ctx.wants_keyboard_input();
+        let (back, forward, up) = ctx.input_mut(|i| {
+            (
+                keyboard && i.consume_key(egui::Modifiers::ALT, Key::ArrowLeft)
+                    || i.pointer.button_pressed(egui::PointerButton::Extra1),
+                keyboard && i.consume_key(egui::Modifiers::ALT, Key::ArrowRight)
+                    || i.pointer.button_pressed(egui::PointerButton::Extra2),
+                keyboard && i.consume_key(egui::Modifiers::ALT, Key::ArrowUp),
+            )
+        });
+        if back {
+            tab.go_back();
+        }
+        if forward {
+            tab.go_forward();
+        }
+        if up {
+            tab.go_up();
+        }
+        let scroll = tab.page_scroll;
+        let page = scroll.viewport_height * 0.9;
+        let line = TEXT_SIZE * self.settings.zoom * 2.;
+        let scroll_to = ctx
+            .input_mut(|i| {
+                let mut key = |modifiers, key| keyboard && i.consume_key(modifiers, key);
+                if key(egui::Modifiers::SHIFT, Key::Space)
+                    || key(egui::Modifiers::NONE, Key::PageUp)
+                {
+                    Some(scroll.offset - page)
+                } else if key(egui::Modifiers::NONE, Key::Space)
+                    || key(egui::Modifiers::NONE, Key::PageDown)
+                {
+                    Some(scroll.offset + page)
+                } else if key(egui::Modifiers::NONE, Key::K) {
+                    Some(scroll.offset - line)
+                } else if key(egui::Modifiers::NONE, Key::J) {
+                    Some(scroll.offset + line)
+                } else if key(egui::Modifiers::NONE, Key::Home) {
+                    Some(0.)
+                } else if key(egui::Modifiers::NONE, Key::End) {
+                    Some(scroll.content_height)
+                } else {
+                    None
+                }
+            })
+            .map(|y| y.clamp(0., (scroll.content_height - scroll.viewport_height).max(0.)));
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, Key::U)) {
+            tab.view_source = !tab.view_source;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, Key::F)) {
+            tab.find.get_or_insert_with(Find::default).focus = true;
+        }
+        if tab.find.is_some() && ctx.input(|i| i.key_pressed(Key::Escape)) {
+            tab.find = None;
+        }
+        if let Some(find) = &mut tab.find {
+            let mut close = false;
+            egui::TopBottomPanel::top("find_bar").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut find.query)
+                            .font(FontId::proportional(TEXT_SIZE))
+                            .text_color(theme.bright_text)
+                            .hint_text("Find in page"),
+                    );
+                    if find.focus {
+                        find.focus = false;
+                        response.request_focus();
+                    }
+                    if response.changed() {
+                        find.restart();
+                    }
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                        if ui.input(|i| i.modifiers.shift) {
+                            find.previous();
+                        } else {
+                            find.next();
+                        }
+                        response.request_focus();
+                    }
+                    if ui
+                        .checkbox(
+                            &mut find.case_sensitive,
+                            RichText::new("Match case")
+                                .size(TEXT_SIZE)
+                                .color(theme.text),
+                        )
+                        .changed()
+                    {
+                        find.restart();
+                    }
+                    ui.label(
+                        RichText::new(format!(
+                            "{}/{}",
+                            if find.count == 0 { 0 } else { find.current + 1 },
+                            find.count
+                        ))
+                        .size(TEXT_SIZE)
+                        .color(theme.text),
+                    );
+                    if ui
+                        .button(RichText::new("^").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        find.previous();
+                    }
+                    if ui
+                        .button(RichText::new("v").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        find.next();
+                    }
+                    if ui
+                        .button(RichText::new("x").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        close = true;
+                    }
+                });
+            });
+            if close {
+                tab.find = None;
+            }
+        }
+
+        if reset_scroll {
+            tab.scroll_to_heading = None;
+        }
+        let page_url = tab
+            .current_url()
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if let Page::Gemtext(gemtext) = &tab.page {
+            let headings: Vec<_> = gemtext
+                .data
+                .iter()
+                .enumerate()
+                .filter_map(|(i, entry)| Some((i, entry.heading()?)))
+                .collect();
+            if self.show_toc && !headings.is_empty() {
+                egui::SidePanel::left("toc")
+                    .resizable(true)
+                    .show(ctx, |ui| {
+                        ui.label(
+                            RichText::new("Contents")
+                                .size(TEXT_SIZE)
+                                .color(theme.bright_text)
+                                .underline(),
+                        );
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            for (i, (level, heading)) in headings {
+                                ui.horizontal(|ui| {
+                                    ui.add_space((level - 1) as f32 * TEXT_SIZE);
+                                    let text = RichText::new(heading)
+                                        .size(TEXT_SIZE / 1.25)
+                                        .color(theme.link);
+                                    let response = ui.link(text);
+                                    if response.clicked() {
+                                        tab.scroll_to_heading = Some(i);
+                                    }
+                                    response.context_menu(|ui| {
+                                        if ui.button("Copy link to heading").clicked() {
+                                            ui.ctx().copy_text(format!(
+                                                "{page_url}#{}",
+                                                gemtext::slug(heading)
+                                            ));
+                                            ui.close_menu();
+                                        }
+                                    });
+                                });
+                            }
+                        });
+                    });
+            }
+        }
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            let text = match (&tab.hovered_link, &tab.status) {
+                (Some(url), _) => url.clone(),
+                (None, Some(status)) => {
+                    let mut text = format!("{} {}  {}", status.code, status.class(), status.meta);
+                    if let Some(retry_at) = tab.retry_at {
+                        let remaining = retry_at.saturating_duration_since(Instant::now());
+                        text.push_str(&format!(
+                            "  (retrying in {}s)",
+                            remaining.as_secs_f32().ceil()
+                        ));
+                    }
+                    text
+                }
+                (None, None) => String::new(),
+            };
+            ui.add(
+                egui::Label::new(RichText::new(text).size(TEXT_SIZE * 0.75).color(theme.text))
+                    .truncate(),
+            );
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Only the page content is zoomed, the bars keep their size
+            let text_size = TEXT_SIZE * self.settings.zoom;
+            let minor_size = MINOR_SIZE * self.settings.zoom;
+            let medium_size = MEDIUM_SIZE * self.settings.zoom;
+            let major_size = MAJOR_SIZE * self.settings.zoom;
+            ui.style_mut().visuals.widgets.inactive.weak_bg_fill = theme.bg;
+            ui.style_mut().visuals.widgets.hovered.weak_bg_fill = theme.bg;
+            if let Some(time) = tab.cached_at {
+                ui.label(
+                    RichText::new(format!(
+                        "Cached copy from {}, the page couldn't be loaded.",
+                        format_age(time)
+                    ))
+                    .size(TEXT_SIZE)
+                    .color(theme.error),
+                );
+            }
+            if let Some(upload) = &mut tab.pending_upload {
+                ui.label(
+                    RichText::new(format!("Upload to {}", percent_decode(&upload.url)))
+                        .size(minor_size)
+                        .color(theme.bright_text),
+                );
+                ui.style_mut().override_font_id = Some(egui::FontId {
+                    size: text_size,
+                    family: egui::FontFamily::Proportional,
+                });
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut upload.token)
+                        .hint_text("Token (optional)")
+                        .password(true)
+                        .text_color(theme.bright_text)
+                        .desired_width(f32::INFINITY),
+                );
+                if reset_scroll {
+                    response.request_focus();
+                }
+                let submit = ui.button("Upload").clicked();
+                egui::ScrollArea::vertical()
+                    .id_salt((tab.id, "upload"))
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut upload.text)
+                                .code_editor()
+                                .text_color(theme.bright_text)
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(20),
+                        );
+                    });
+                ui.style_mut().override_font_id = None;
+                if submit {
+                    remilog!(
+                        "[TITAN] Uploading {} bytes to '{}'",
+                        upload.text.len(),
+                        upload.url
+                    );
+                    tab.loading = Some(Loading::upload(
+                        tab.server_name.clone(),
+                        upload.url.clone(),
+                        std::mem::take(&mut upload.text).into_bytes(),
+                        std::mem::take(&mut upload.token),
+                        self.known_hosts.clone(),
+                        self.identities.get(&tab.server_name).cloned(),
+                        self.settings.request_options,
+                    ));
+                    tab.pending_upload = None;
+                }
+                return;
+            }
+            if let Some(input) = &mut tab.pending_input {
+                ui.label(
+                    RichText::new(&input.prompt)
+                        .size(minor_size)
+                        .color(theme.bright_text),
+                );
+                ui.style_mut().override_font_id = Some(egui::FontId {
+                    size: text_size,
+                    family: egui::FontFamily::Proportional,
+                });
+                let text_edit = egui::TextEdit::singleline(&mut input.text)
+                    .password(matches!(input.kind, InputKind::Sensitive))
+                    .text_color(theme.bright_text)
+                    .desired_width(f32::INFINITY);
+                let response = ui.add(text_edit);
+                ui.style_mut().override_font_id = None;
+                if reset_scroll {
+                    response.request_focus();
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    // The input replaces any query the prompting URL already had
+                    match GeminiUrl::parse(&input.url) {
+                        Ok(url) => {
+                            let url = url.with_query(&percent_encode(&input.text)).to_string();
+                            tab.navigate(&url);
+                        }
+                        Err(e) => {
+                            remilog!("[INPUT ERROR] Couldn't submit the input: {e}");
+                            tab.pending_input = None;
+                            tab.show_error("Couldn't submit the input".to_string(), e.to_string());
+                        }
+                    }
+                }
+                return;
+            }
+            let mut scroll_area = egui::ScrollArea::vertical()
+                .id_salt(tab.id)
+                .auto_shrink(false)
+                .stick_to_right(true);
+            if reset_scroll {
+                scroll_area = scroll_area.scroll_offset(egui::Vec2 { x: 0., y: 0. })
+            } else if let Some(y) = scroll_to {
+                scroll_area = scroll_area.vertical_scroll_offset(y);
+            }
+            let output = scroll_area.show_viewport(ui, |ui, viewport| {
+                ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Wrap);
+                let visible = viewport.translate(ui.max_rect().min.to_vec2());
+                // Lets screen readers pick a voice for the language of the page
+                if let Some(lang) = &tab.lang {
+                    ui.ctx().accesskit_node_builder(ui.id(), |node| {
+                        node.set_language(lang.clone());
+                    });
+                }
+                tab.links.clear();
+                tab.hovered_link = None;
+                let mut clicked_link = None;
+                let page_url = tab.current_url().clone();
+                let mut saved_link = None;
+                let mut background_link = None;
+                // The selected match is scrolled to again once its entry is rendered
+                let mut find_pending = false;
+                if let Some(find) = &mut tab.find {
+                    find.seen = 0;
+                    if reset_scroll {
+                        find.current = 0;
+                        find.scroll = false;
+                    }
+                }
+                // Reader mode narrows the page to a centered column, preformatted blocks scroll within it
+                let column_width = if self.settings.reader_mode {
+                    let char_width =
+                        ui.fonts(|f| f.glyph_width(&FontId::proportional(text_size), '0'));
+                    (self.settings.reader_width as f32 * char_width).min(ui.available_width())
+                } else {
+                    ui.available_width()
+                };
+                centered_column(ui, column_width, |ui| match &tab.page {
+                    _ if tab.view_source => {
+                        if tab.raw_response.is_empty() {
+                            ui.label(
+                                RichText::new("The source of this page isn't available.")
+                                    .size(text_size)
+                                    .color(theme.text),
+                            );
+                        }
+                        let raw = String::from_utf8_lossy(&tab.raw_response);
+                        let (header, body) = raw.split_once("\r\n").unwrap_or((&raw, ""));
+                        ui.label(
+                            RichText::new(header)
+                                .font(FontId::monospace(text_size))
+                                .color(theme.bright_text),
+                        );
+                        ui.separator();
+                        let (text, scroll) = find::highlight(
+                            &mut tab.find,
+                            body,
+                            FontId::monospace(text_size),
+                            theme.preformatted_text,
+                            Color32::TRANSPARENT,
+                        );
+                        let response = ui.add(egui::Label::new(text).selectable(true));
+                        if scroll {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                        }
+                    }
+                    Page::Gemtext(gemtext) => {
+                        let mut scroll_fragment = if reset_scroll {
+                            None
+                        } else {
+                            tab.scroll_fragment.clone()
+                        };
+                        // The heading is scrolled to again once it's rendered
+                        let mut heading_pending = false;
+                        // Right-to-left languages are read from the right edge
+                        let align = if tab.lang.as_deref().is_some_and(response::is_rtl) {
+                            egui::Align::Max
+                        } else {
+                            egui::Align::Min
+                        };
+                        let row_height = |font_id| ui.fonts(|f| f.row_height(&font_id));
+                        let rows = RowHeights {
+                            text: row_height(FontId::proportional(text_size)),
+                            monospace: row_height(FontId::monospace(text_size)),
+                            minor_heading: row_height(FontId::proportional(minor_size)),
+                            medium_heading: row_height(FontId::proportional(medium_size)),
+                            major_heading: row_height(FontId::proportional(major_size)),
+                            spacing: ui.spacing().item_spacing.y,
+                        };
+                        tab.page_layout.update(LayoutKey {
+                            page: tab.page_generation,
+                            entries: gemtext.data.len(),
+                            width: column_width,
+                            text_size,
+                            theme,
+                            right_to_left: align == egui::Align::Max,
+                            wrap_text: self.wrap_text,
+                            linkify_text: self.settings.linkify_text,
+                        });
+                        // List bullets and quote bars stay on the side the text starts from
+                        let row_layout = if align == egui::Align::Max {
+                            egui::Layout::right_to_left(egui::Align::Min)
+                        } else {
+                            egui::Layout::left_to_right(egui::Align::Min)
+                        };
+                        ui.with_layout(egui::Layout::top_down(align), |ui| {
+                            let mut previous_blank = false;
+                            let mut y = ui.cursor().top();
+                            // Height of the entries out of view that the next rendered one goes after
+                            let mut skipped = 0.;
+                            for (i, g) in gemtext.data.iter().enumerate() {
+                                // Only the rendering skips them, the source is left untouched
+                                let blank = match g {
+                                    gemtext::GemtextEntry::Text(t) => t.trim().is_empty(),
+                                    _ => false,
+                                };
+                                if blank && previous_blank && self.settings.collapse_blank_lines {
+                                    continue;
+                                }
+                                previous_blank = blank;
+                                let height = tab.page_layout.height(i, g, rows);
+                                if y + height < visible.top() || y > visible.bottom() {
+                                    // Entries out of view only take their space, and keep the link
+                                    // numbers and match count the same as if they were rendered
+                                    let rect = egui::Rect::from_min_size(
+                                        egui::pos2(ui.max_rect().left(), y),
+                                        egui::vec2(column_width, height),
+                                    );
+                                    // Scrolling to them jumps, an animation would start over every
+                                    // frame until they're rendered
+                                    let scroll_to_entry = |align| {
+                                        ui.scroll_to_rect_animation(
+                                            rect,
+                                            Some(align),
+                                            egui::style::ScrollAnimation::none(),
+                                        )
+                                    };
+                                    y += height;
+                                    skipped += height;
+                                    if let gemtext::GemtextEntry::Link { url, .. } = g {
+                                        tab.links.push(url.clone());
+                                    }
+                                    let fragment_heading = g.heading().is_some_and(|(_, h)| {
+                                        scroll_fragment
+                                            .take_if(|fragment| heading_matches(h, fragment))
+                                            .is_some()
+                                    });
+                                    if fragment_heading || tab.scroll_to_heading == Some(i) {
+                                        scroll_to_entry(egui::Align::TOP);
+                                        heading_pending = true;
+                                    }
+                                    if let Some(find) = &mut tab.find {
+                                        let count =
+                                            entry_matches(find, g, self.settings.linkify_text);
+                                        if find.scroll
+                                            && (find.seen..find.seen + count)
+                                                .contains(&find.current)
+                                        {
+                                            scroll_to_entry(egui::Align::Center);
+                                            find_pending = true;
+                                        }
+                                        find.seen += count;
+                                    }
+                                    continue;
+                                }
+                                ui.add_space(skipped);
+                                skipped = 0.;
+                                let top = ui.cursor().top();
+                                match g {
+                                    gemtext::GemtextEntry::Text(t) => {
+                                        // Only the rendering is split, the entry stays plain text
+                                        let spans = if self.settings.linkify_text {
+                                            gemtext::linkify(t)
+                                        } else {
+                                            None
+                                        };
+                                        let mut text_label = |ui: &mut egui::Ui| match &spans {
+                                            Some(spans) => linkified_label(
+                                                ui,
+                                                &mut tab.find,
+                                                spans,
+                                                &page_url,
+                                                text_size,
+                                                theme,
+                                            ),
+                                            None => {
+                                                entry_label(
+                                                    ui,
+                                                    &mut tab.page_layout,
+                                                    i,
+                                                    &mut tab.find,
+                                                    t,
+                                                    text_size,
+                                                    theme.text,
+                                                );
+                                                vec![]
+                                            }
+                                        };
+                                        let links = if self.wrap_text {
+                                            text_label(ui)
+                                        } else {
+                                            egui::ScrollArea::horizontal()
+                                                .id_salt(i)
+                                                .auto_shrink([false, true])
+                                                .drag_to_scroll(false)
+                                                .min_scrolled_width(0.)
+                                                .show(ui, |ui| {
+                                                    ui.style_mut().wrap_mode =
+                                                        Some(egui::TextWrapMode::Extend);
+                                                    text_label(ui)
+                                                })
+                                                .inner
+                                        };
+                                        for (url, response) in links {
+                                            if response.hovered() {
+                                                tab.hovered_link = Some(url.to_string());
+                                            }
+                                            if response.middle_clicked()
+                                                && url.starts_with("gemini://")
+                                            {
+                                                background_link = Some(url.to_string());
+                                            } else if response.clicked() {
+                                                clicked_link = Some(url.to_string());
+                                            }
+                                        }
+                                    }
+                                    gemtext::GemtextEntry::Link { url, label } => {
+                                        tab.links.push(url.clone());
+                                        ui.horizontal(|ui| {
+                                            ui.label(
+                                                RichText::new(format!("[{}]", tab.links.len()))
+                                                    .size(text_size)
+                                                    .color(theme.text),
+                                            );
+                                            let external = url::scheme(url).is_some_and(|scheme| {
+                                                !matches!(scheme.as_str(), "gemini" | "spartan")
+                                            });
+                                            let insecure = is_insecure_link(&page_url, url);
+                                            if insecure {
+                                                ui.label(
+                                                    RichText::new("⚠")
+                                                        .size(text_size)
+                                                        .color(theme.insecure_link),
+                                                );
+                                            }
+                                            let text = RichText::new(url).size(text_size).color(
+                                                if insecure {
+                                                    theme.insecure_link
+                                                } else if external {
+                                                    theme.external_link
+                                                } else {
+                                                    theme.link
+                                                },
+                                            );
+                                            let mut response = ui.link(text);
+                                            if insecure {
+                                                response =
+                                                    response.on_hover_text(INSECURE_LINK_HINT);
+                                            } else if external {
+                                                response = response.on_hover_text(
+                                                    "Opens outside of remi when supported",
+                                                );
+                                            }
+                                            if response.hovered() {
+                                                tab.hovered_link =
+                                                    Some(resolve_url(&tab.request_data, url));
+                                            }
+                                            // Middle or Ctrl+click opens the link in a background tab
+                                            if !external
+                                                && (response.middle_clicked()
+                                                    || response.clicked()
+                                                        && ui.input(|i| i.modifiers.command))
+                                            {
+                                                background_link = Some(url.clone());
+                                            } else if response.clicked() {
+                                                clicked_link = Some(url.clone());
+                                            }
+                                            response.context_menu(|ui| {
+                                                if !external
+                                                    && ui.button("Open in new tab").clicked()
+                                                {
+                                                    background_link = Some(url.clone());
+                                                    ui.close_menu();
+                                                }
+                                                if ui.button("Copy link URL").clicked() {
+                                                    ui.ctx().copy_text(resolve_url(
+                                                        &tab.request_data,
+                                                        url,
+                                                    ));
+                                                    ui.close_menu();
+                                                }
+                                                if !external
+                                                    && ui.button("Save link target as...").clicked()
+                                                {
+                                                    saved_link = Some(url.clone());
+                                                    ui.close_menu();
+                                                }
+                                            });
+                                            find_label(
+                                                ui,
+                                                &mut tab.find,
+                                                label,
+                                                text_size,
+                                                theme.text,
+                                            );
+                                        });
+                                    }
+                                    gemtext::GemtextEntry::MinorHeading(h)
+                                    | gemtext::GemtextEntry::MediumHeading(h)
+                                    | gemtext::GemtextEntry::MajorHeading(h) => {
+                                        let size = match g {
+                                            gemtext::GemtextEntry::MinorHeading(_) => minor_size,
+                                            gemtext::GemtextEntry::MediumHeading(_) => medium_size,
+                                            _ => major_size,
+                                        };
+                                        let response = entry_label(
+                                            ui,
+                                            &mut tab.page_layout,
+                                            i,
+                                            &mut tab.find,
+                                            h,
+                                            size,
+                                            theme.heading,
+                                        );
+                                        if scroll_fragment
+                                            .take_if(|fragment| heading_matches(h, fragment))
+                                            .is_some()
+                                            || tab.scroll_to_heading == Some(i)
+                                        {
+                                            ui.scroll_to_rect(
+                                                response.rect,
+                                                Some(egui::Align::TOP),
+                                            );
+                                        }
+                                    }
+                                    gemtext::GemtextEntry::List(elems) => {
+                                        // Wrapped lines are indented past the bullet
+                                        // Items are closer to each other than to the blocks around the list
+                                        let gap = text_size / 4.;
+                                        ui.add_space(gap);
+                                        ui.scope(|ui| {
+                                            ui.spacing_mut().item_spacing.y = gap / 2.;
+                                            for el in elems {
+                                                ui.with_layout(row_layout, |ui| {
+                                                    ui.label(
+                                                        RichText::new("•")
+                                                            .size(text_size)
+                                                            .color(theme.list),
+                                                    );
+                                                    find_label(
+                                                        ui,
+                                                        &mut tab.find,
+                                                        el,
+                                                        text_size,
+                                                        theme.list,
+                                                    );
+                                                });
+                                            }
+                                        });
+                                        ui.add_space(gap);
+                                    }
+                                    gemtext::GemtextEntry::Quote(q) => {
+                                        let bar_width = text_size / 8.;
+                                        let response = ui.with_layout(row_layout, |ui| {
+                                            ui.add_space(bar_width + text_size / 2.);
+                                            let (text, scroll) = entry_text(
+                                                ui,
+                                                &mut tab.page_layout,
+                                                i,
+                                                &mut tab.find,
+                                                &q.join("\n"),
+                                                TextFormat {
+                                                    italics: true,
+                                                    ..TextFormat::simple(
+                                                        FontId::proportional(text_size),
+                                                        theme.text,
+                                                    )
+                                                },
+                                            );
+                                            let response = ui.label(text);
+                                            if scroll {
+                                                response.scroll_to_me(Some(egui::Align::Center));
+                                            }
+                                        });
+                                        let rect = response.response.rect;
+                                        let bar = if align == egui::Align::Max {
+                                            rect.with_min_x(rect.max.x - bar_width)
+                                        } else {
+                                            rect.with_max_x(rect.min.x + bar_width)
+                                        };
+                                        ui.painter().rect_filled(bar, 0, theme.quote_bar);
+                                    }
+                                    gemtext::GemtextEntry::Preformatted { alt_text, body } => {
+                                        let block = (page_url.clone(), i);
+                                        let mut open = !self.folded_blocks.contains(&block);
+                                        let matches = tab
+                                            .find
+                                            .as_ref()
+                                            .map_or(0, |find| find.matches(body).len());
+                                        // The selected match is shown even if its block was folded
+                                        if tab.find.as_ref().is_some_and(|find| {
+                                            find.scroll
+                                                && (find.seen..find.seen + matches)
+                                                    .contains(&find.current)
+                                        }) {
+                                            open = true;
+                                            self.folded_blocks.remove(&block);
+                                        }
+                                        let header = preformatted_block(
+                                            ui,
+                                            &mut tab.page_layout,
+                                            &mut tab.find,
+                                            i,
+                                            alt_text,
+                                            body,
+                                            open,
+                                            text_size,
+                                            theme,
+                                        );
+                                        // The matches of a folded block still count, for the numbering of the next ones
+                                        if header.body_returned.is_none() {
+                                            if let Some(find) = &mut tab.find {
+                                                find.seen += matches;
+                                            }
+                                        }
+                                        if header.header_response.clicked() {
+                                            if open {
+                                                self.folded_blocks.insert(block);
+                                            } else {
+                                                self.folded_blocks.remove(&block);
+                                            }
+                                        }
+                                    }
+                                }
+                                y = ui.cursor().top();
+                                tab.page_layout.set_height(i, y - top);
+                            }
+                            ui.add_space(skipped);
+                        });
+                        if !heading_pending {
+                            if !reset_scroll {
+                                tab.scroll_fragment = None;
+                            }
+                            tab.scroll_to_heading = None;
+                        }
+                    }
+                    Page::Text(body) => {
+                        let (text, scroll) = find::highlight(
+                            &mut tab.find,
+                            body,
+                            FontId::monospace(text_size),
+                            theme.text,
+                            Color32::TRANSPARENT,
+                        );
+                        let response = ui.add(egui::Label::new(text).selectable(true));
+                        if scroll {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                        }
+                    }
+                    Page::Settings => self.settings_ui(ui),
+                    Page::ClientCertificate { title, msg } => {
+                        ui.label(RichText::new(title).size(medium_size).color(theme.error));
+                        ui.label(RichText::new(msg).size(text_size).color(theme.text));
+                        let scope = self.identities.get(&tab.server_name).map(|i| i.scope);
+                        let has_identity = scope.is_some();
+                        ui.label(
+                            RichText::new(match scope {
+                                Some(CertScope::Persistent) => format!(
+                                    "The identity configured for '{}' was rejected.",
+                                    tab.server_name
+                                ),
+                                Some(CertScope::Transient) => format!(
+                                    "The identity used for '{}' in this session was rejected.",
+                                    tab.server_name
+                                ),
+                                None => {
+                                    format!("No identity is configured for '{}'.", tab.server_name)
+                                }
+                            })
+                            .size(text_size)
+                            .color(theme.text),
+                        );
+                        ui.horizontal(|ui| {
+                            for (scope, text) in [
+                                (CertScope::Persistent, "Keep the new identity"),
+                                (CertScope::Transient, "Only use it in this session"),
+                            ] {
+                                ui.radio_value(
+                                    &mut self.identity_scope,
+                                    scope,
+                                    RichText::new(text).size(text_size).color(theme.text),
+                                );
+                            }
+                        });
+                        let mut new_identity = None;
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button(
+                                    RichText::new("Choose certificate files...")
+                                        .size(text_size)
+                                        .color(theme.text),
+                                )
+                                .clicked()
+                            {
+                                if let (Some(cert), Some(key)) = (
+                                    rfd::FileDialog::new()
+                                        .set_title("Client certificate (PEM)")
+                                        .pick_file(),
+                                    rfd::FileDialog::new()
+                                        .set_title("Private key (PEM)")
+                                        .pick_file(),
+                                ) {
+                                    new_identity = Some(ClientIdentity::from_files(
+                                        &cert,
+                                        &key,
+                                        self.identity_scope,
+                                    ));
+                                }
+                            }
+                            if ui
+                                .button(
+                                    RichText::new("Generate new identity")
+                                        .size(text_size)
+                                        .color(theme.text),
+                                )
+                                .clicked()
+                            {
+                                new_identity = Some(ClientIdentity::generate(
+                                    &tab.server_name,
+                                    self.identity_scope,
+                                ));
+                            }
+                            if has_identity
+                                && ui
+                                    .button(
+                                        RichText::new("Remove identity")
+                                            .size(text_size)
+                                            .color(theme.text),
+                                    )
+                                    .clicked()
+                            {
+                                if let Err(e) = self.identities.remove(&tab.server_name) {
+                                    remilog!("[IDENTITY ERROR] Couldn't remove identity: {e}");
+                                }
+                            }
+                        });
+                        match new_identity
+                            .map(|i| i.and_then(|i| self.identities.insert(&tab.server_name, i)))
+                        {
+                            // Retry the request now that there is an identity to present
+                            Some(Ok(())) => tab.redir = true,
+                            Some(Err(e)) => {
+                                remilog!("[IDENTITY ERROR] Couldn't set up identity: {e}")
+                            }
+                            None => (),
+                        }
+                    }
+                    Page::Error { title, msg, detail } => {
+                        ui.label(RichText::new(title).size(medium_size).color(theme.error));
+                        ui.label(RichText::new(msg).size(text_size).color(theme.text));
+                        if let Some(detail) = detail {
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(detail)
+                                        .font(FontId::monospace(text_size * 0.75))
+                                        .color(theme.preformatted_text)
+                                        .background_color(theme.preformatted_bg),
+                                )
+                                .selectable(true)
+                                .extend(),
+                            );
+                        }
+                        if ui
+                            .button(RichText::new("Go back").size(text_size).color(theme.text))
+                            .clicked()
+                        {
+                            tab.leave_error();
+                        }
+                        // Servers that are down, or whose scripts or proxies failed, may recover
+                        let transient = tab
+                            .status
+                            .as_ref()
+                            .is_some_and(|status| matches!(status.code, 41..=43));
+                        if transient
+                            && ui
+                                .button(RichText::new("Retry").size(text_size).color(theme.text))
+                                .clicked()
+                        {
+                            tab.retry();
+                        }
+                        // A 52 Gone response means a bookmark to the page is dead for good
+                        let gone = tab.status.as_ref().is_some_and(|status| status.code == 52);
+                        if gone
+                            && self.bookmarks.iter().any(|b| b.url == tab.request_data)
+                            && ui
+                                .button(
+                                    RichText::new("Remove from bookmarks")
+                                        .size(text_size)
+                                        .color(theme.text),
+                                )
+                                .clicked()
+                        {
+                            self.bookmarks.retain(|b| b.url != tab.request_data);
+                        }
+                        if let Some(copy) = &tab.offline_copy {
+                            if ui
+                                .button(
+                                    RichText::new(format!(
+                                        "Show cached copy from {}",
+                                        format_age(copy.time)
+                                    ))
+                                    .size(text_size)
+                                    .color(theme.text),
+                                )
+                                .clicked()
+                            {
+                                tab.loading = Some(Loading::cached(copy.raw.clone()));
+                                tab.cached_at = Some(copy.time);
+                                tab.offline_copy = None;
+                            }
+                        }
+                    }
+                    Page::Image {
+                        mime,
+                        data,
+                        texture: Ok(texture),
+                    } => {
+                        let size = texture.size_vec2() * self.settings.zoom;
+                        let scale = (ui.available_width() / size.x).min(1.);
+                        ui.add(egui::Image::from_texture(texture).fit_to_exact_size(size * scale))
+                            .on_hover_text(mime.as_str())
+                            .context_menu(|ui| {
+                                if ui.button("Save image as...").clicked() {
+                                    save_to_file(&tab.request_data, data);
+                                    ui.close_menu();
+                                }
+                            });
+                    }
+                    Page::Image {
+                        mime,
+                        data,
+                        texture: Err(e),
+                    } => {
+                        ui.label(
+                            RichText::new(format!(
+                                "Image of type '{mime}' can't be displayed: {e}"
+                            ))
+                            .size(text_size)
+                            .color(theme.error),
+                        );
+                        if ui
+                            .button(
+                                RichText::new("Save as...")
+                                    .size(text_size)
+                                    .color(theme.text),
+                            )
+                            .clicked()
+                        {
+                            save_to_file(&tab.request_data, data);
+                        }
+                    }
+                    Page::Binary { mime, data } => {
+                        ui.label(
+                            RichText::new(format!("Content of type '{mime}' can't be displayed."))
+                                .size(text_size)
+                                .color(theme.text),
+                        );
+                        if ui
+                            .button(
+                                RichText::new("Save as...")
+                                    .size(text_size)
+                                    .color(theme.text),
+                            )
+                            .clicked()
+                        {
+                            save_to_file(&tab.request_data, data);
+                        }
+                    }
+                });
+                if let Some(find) = &mut tab.find {
+                    if find.count != find.seen {
+                        find.count = find.seen;
+                        find.current = find.current.min(find.count.saturating_sub(1));
+                        // The match count shown in the find bar is one frame late
+                        ui.ctx().request_repaint();
+                    }
+                    find.scroll = find_pending;
+                }
+                if let Some(url) = clicked_link {
+                    tab.follow_link(&url);
+                }
+                if let Some(url) = saved_link {
+                    self.save_link_target(&tab, &url);
+                }
+                if let Some(url) = background_link {
+                    self.tabs.push(tab.open_link(&url));
+                }
+                ui.style_mut().wrap_mode = None;
+            });
+            tab.page_scroll = PageScroll {
+                offset: output.state.offset.y,
+                viewport_height: output.inner_rect.height(),
+                content_height: output.content_size.y,
+            };
+        });
+
+        if let Some(mismatch) = &tab.cert_mismatch {
+            let mut trust = None;
+            egui::Modal::new(egui::Id::new("cert_mismatch")).show(ctx, |ui| {
+                ui.label(
+                    RichText::new("Certificate changed")
+                        .size(MINOR_SIZE)
+                        .color(theme.error),
+                );
+                ui.label(
+                    RichText::new(format!(
+                        "'{}' presented a different certificate than the one trusted before.",
+                        mismatch.host
+                    ))
+                    .size(TEXT_SIZE)
+                    .color(theme.text),
+                );
+                ui.label(
+                    RichText::new(format!("Old: {}\nNew: {}", mismatch.old, mismatch.new))
+                        .size(TEXT_SIZE / 1.5)
+                        .color(theme.bright_text)
+                        .monospace(),
+                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(
+                            RichText::new("Trust new certificate")
+                                .size(TEXT_SIZE)
+                                .color(theme.text),
+                        )
+                        .clicked()
+                    {
+                        trust = Some(true);
+                    }
+                    if ui
+                        .button(RichText::new("Cancel").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        trust = Some(false);
+                    }
+                });
+            });
+            match trust {
+                Some(true) => {
+                    self.known_hosts
+                        .lock()
+                        .unwrap()
+                        .fingerprints
+                        .insert(mismatch.host.clone(), mismatch.new.clone());
+                    tab.cert_mismatch = None;
+                    tab.redir = true;
+                }
+                Some(false) => {
+                    tab.cert_mismatch = None;
+                    tab.moving_in_history = false;
+                    tab.restore_from_history();
+                    tab.url_bar_data = decode_for_display(&tab.request_data);
+                }
+                None => (),
+            }
+        }
+
+        if let Some(pending) = &tab.pending_redirect {
+            let mut follow = None;
+            egui::Modal::new(egui::Id::new("cross_host_redirect")).show(ctx, |ui| {
+                ui.label(
+                    RichText::new("Redirection to another host")
+                        .size(MINOR_SIZE)
+                        .color(theme.text),
+                );
+                ui.label(
+                    RichText::new(format!(
+                        "'{}' is redirecting you to '{}', continue?",
+                        pending.from, pending.server_name
+                    ))
+                    .size(TEXT_SIZE)
+                    .color(theme.text),
+                );
+                ui.label(
+                    RichText::new(percent_decode(&pending.request_data))
+                        .size(TEXT_SIZE / 1.5)
+                        .color(theme.bright_text)
+                        .monospace(),
+                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Continue").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        follow = Some(true);
+                    }
+                    if ui
+                        .button(RichText::new("Cancel").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        follow = Some(false);
+                    }
+                });
+            });
+            match follow {
+                Some(true) => {
+                    let pending = tab.pending_redirect.take().unwrap();
+                    tab.server_name = pending.server_name;
+                    tab.request_data = pending.request_data;
+                    tab.url_bar_data = decode_for_display(&tab.request_data);
+                    tab.redir = true;
+                }
+                Some(false) => {
+                    tab.pending_redirect = None;
+                    tab.redirects.clear();
+                    tab.redirect_origin = None;
+                    tab.moving_in_history = false;
+                    tab.restore_from_history();
+                    tab.url_bar_data = decode_for_display(&tab.request_data);
+                }
+                None => (),
+            }
+        }
+
+        if let Some(url) = &tab.pending_insecure_link {
+            let mut open = None;
+            egui::Modal::new(egui::Id::new("insecure_link")).show(ctx, |ui| {
+                ui.label(
+                    RichText::new("Unencrypted link")
+                        .size(MINOR_SIZE)
+                        .color(theme.insecure_link),
+                );
+                ui.label(
+                    RichText::new(
+                        "This link leaves the encrypted Gemini space, \
+                        the page will be requested over plain http. Open it outside of remi?",
+                    )
+                    .size(TEXT_SIZE)
+                    .color(theme.text),
+                );
+                ui.label(
+                    RichText::new(url)
+                        .size(TEXT_SIZE / 1.5)
+                        .color(theme.bright_text)
+                        .monospace(),
+                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Open").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        open = Some(true);
+                    }
+                    if ui
+                        .button(RichText::new("Cancel").size(TEXT_SIZE).color(theme.text))
+                        .clicked()
+                    {
+                        open = Some(false);
+                    }
+                });
+            });
+            match open {
+                Some(true) => {
+                    let url = tab.pending_insecure_link.take().unwrap();
+                    tab.open_external(&url);
+                }
+                Some(false) => tab.pending_insecure_link = None,
+                None => (),
+            }
+        }
+
+        // Follow numbered links by typing their number and pressing Enter
+        if ctx.wants_keyboard_input() {
+            self.link_number.clear();
+        } else {
+            ctx.input(|i| {
+                for event in &i.events {
+                    if let egui::Event::Text(t) = event {
+                        self.link_number
+                            .extend(t.chars().filter(|c| c.is_ascii_digit()));
+                    }
+                }
+                if i.key_pressed(Key::Escape) {
+                    self.link_number.clear();
+                }
+                if i.key_pressed(Key::Enter) && !self.link_number.is_empty() {
+                    match self.link_number.parse::<usize>() {
+                        Ok(n) if n >= 1 && n <= tab.links.len() => {
+                            tab.follow_link(&tab.links[n - 1].clone());
+                        }
+                        _ => remilog!(
+                            "[LINK ERROR] There is no link numbered {}.",
+                            self.link_number
+                        ),
+                    }
+                    self.link_number.clear();
+                }
+            });
+        }
+        self.tabs.insert(self.active_tab, tab);
+    }
+}
+
+/// `url` without its fragment, which doesn't change the response
+fn cache_key(url: &str) -> &str {
+    url.split_once('#').map_or(url, |(url, _)| url)
+}
+
+/// How long ago `time` was, e.g. `3 hours ago`
+fn format_age(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    let (count, unit) = match secs {
+        0..60 => return "less than a minute ago".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" })
+}
+
+/// Emoji of a `/favicon.txt` body, which must hold nothing else
+fn parse_favicon(body: &str) -> Option<String> {
+    let favicon = body.trim();
+    // Emoji sequences may join several code points, but never that many
+    let valid = !favicon.is_empty()
+        && favicon.chars().count() <= 10
+        && !favicon.is_ascii()
+        && !favicon.contains(char::is_whitespace);
+    valid.then(|| favicon.to_string())
+}
+
+/// Page listing the unread entries of `subscriptions`
+fn subscriptions_page(subscriptions: &[Subscription], checking: bool) -> Page {
+    let mut gemtext = subscriptions::to_gemtext(subscriptions);
+    if subscriptions.is_empty() {
+        gemtext.data.push(GemtextEntry::Text(
+            "No subscriptions yet, use 'Subscribe to this page' in the bookmarks menu.".to_string(),
+        ));
+    } else if checking {
+        gemtext.data.insert(
+            1,
+            GemtextEntry::Text("Checking for new entries...".to_string()),
+        );
+    }
+    Page::Gemtext(gemtext)
+}
+
+/// Number of history entries of every host visited in `tabs`, most visited first
+fn host_visits(tabs: &[Tab]) -> Vec<(String, usize)> {
+    let mut visits: Vec<(String, usize)> = vec![];
+    let urls = tabs.iter().flat_map(|tab| &tab.history).map(|(_, url)| url);
+    for url in urls.filter_map(|url| GeminiUrl::parse(url).ok()) {
+        let host = url.authority();
+        match visits.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, count)) => *count += 1,
+            None => visits.push((host, 1)),
+        }
+    }
+    visits.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    visits
+}
+
+/// Page listing `visits` as links to the root of every host
+fn stats_page(visits: &[(String, usize)]) -> Page {
+    let mut data = vec![
+        GemtextEntry::MajorHeading("Most visited".to_string()),
+        GemtextEntry::Text("Visits are counted from the history of the open tabs.".to_string()),
+    ];
+    data.extend(visits.iter().map(|(host, count)| GemtextEntry::Link {
+        url: format!("gemini://{host}/"),
+        label: format!(
+            "{host} ({count} {})",
+            if *count == 1 { "visit" } else { "visits" }
+        ),
+    }));
+    Page::Gemtext(Gemtext { data })
+}
+
+/// Pages of `history`, most recent first. `current` is the index of the one that was shown.
+fn history_page(history: &History, current: usize) -> Page {
+    let mut data = vec![GemtextEntry::MajorHeading("History".to_string())];
+    if history.is_empty() {
+        data.push(GemtextEntry::Text(
+            "No page was visited in this tab yet.".to_string(),
+        ));
+    }
+    data.extend(
+        history
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, (_, url))| GemtextEntry::Link {
+                url: url.clone(),
+                label: if i == current {
+                    format!("{url} (current)")
+                } else {
+                    url.clone()
+                },
+            }),
+    );
+    Page::Gemtext(Gemtext { data })
+}
+
+/// Serializes the history of every tab, separated by empty lines, after the index of the active tab
+fn tabs_to_lines(tabs: &[Tab], active_tab: usize) -> String {
+    let mut res = active_tab.to_string();
+    for tab in tabs {
+        res.push_str("\n\n");
+        res.push_str(&history_to_lines(&tab.history, tab.loaded_history_index));
+    }
+    res
+}
+
+/// Histories and current indices of the tabs serialized by `tabs_to_lines`, with the index of the active tab
+fn tabs_from_lines(lines: &str) -> (Vec<(History, usize)>, usize) {
+    let mut blocks = lines.split("\n\n");
+    let active_tab = blocks
+        .next()
+        .and_then(|l| l.trim().parse().ok())
+        .unwrap_or(0);
+    let tabs: Vec<_> = blocks
+        .map(history_from_lines)
+        .filter(|(history, _)| !history.is_empty())
+        .collect();
+    let active_tab = active_tab.min(tabs.len().saturating_sub(1));
+    (tabs, active_tab)
+}
+
+/// Serializes the last `MAX_STORED_HISTORY` entries of `history`, the first line is the index of the current entry
+fn history_to_lines(history: &[(String, String)], index: usize) -> String {
+    let skipped = history.len().saturating_sub(MAX_STORED_HISTORY);
+    let mut res = index.saturating_sub(skipped).to_string();
+    for (server_name, request_data) in &history[skipped..] {
+        res.push('\n');
+        res.push_str(server_name);
+        res.push(' ');
+        res.push_str(request_data);
+    }
+    res
+}
+
+fn history_from_lines(lines: &str) -> (History, usize) {
+    let mut lines = lines.lines();
+    let index = lines.next().and_then(|l| l.parse().ok()).unwrap_or(0);
+    let history: Vec<_> = lines
+        .filter_map(|l| l.split_once(' '))
+        .map(|(server_name, request_data)| (server_name.to_string(), request_data.to_string()))
+        .collect();
+    let index = index.min(history.len().saturating_sub(1));
+    (history, index)
+}
+
+/// Loads the theme file from the storage directory, an invalid file is reported and ignored
+fn load_custom_theme() -> Option<Theme> {
+    let path = eframe::storage_dir("remi")?.join(theme::THEME_FILE_NAME);
+    let raw = std::fs::read_to_string(&path).ok()?;
+    match Theme::from_toml(&raw) {
+        Ok(theme) => Some(theme),
+        Err(e) => {
+            remilog!("[THEME ERROR] '{}' is invalid, {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Asks the user for a path and writes the bookmarks there as a Gemtext file
+fn export_bookmarks(bookmarks: &[Bookmark]) {
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter("Gemtext", &["gmi", "gemini"])
+        .set_file_name("bookmarks.gmi")
+        .save_file()
+    {
+        let gemtext = bookmarks::to_gemtext(bookmarks).to_string() + "\n";
+        if let Err(e) = std::fs::write(&path, gemtext) {
+            remilog!("[EXPORT ERROR] Couldn't write '{}': {e}", path.display());
+        }
+    }
+}
+
+/// Adds the links of a Gemtext file picked by the user to `bookmarks`
+fn import_bookmarks(bookmarks: &mut Vec<Bookmark>) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Gemtext", &["gmi", "gemini"])
+        .pick_file()
+    else {
+        return;
+    };
+    let raw = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            remilog!("[IMPORT ERROR] Couldn't read '{}': {e}", path.display());
+            return;
+        }
+    };
+    match Gemtext::from_str(&raw) {
+        Ok(gemtext) => {
+            let added = bookmarks::merge(bookmarks, bookmarks::from_gemtext(&gemtext));
+            remilog!(
+                "[IMPORT] Added {added} bookmarks from '{}'.",
+                path.display()
+            );
+        }
+        Err(e) => remilog!("[IMPORT ERROR] Couldn't parse '{}': {e}", path.display()),
+    }
+}
+
+/// Last path segment of `url`, suggested as the name of the file it is saved to
+fn file_name(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    percent_decode(
+        url.trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default(),
+    )
+}
+
+/// Decodes an image response into a texture.
+/// Images bigger than `MAX_IMAGE_SIZE` on either side are rejected instead of exhausting the memory.
+fn load_image(ctx: &egui::Context, data: &[u8]) -> Result<egui::TextureHandle, String> {
+    let mut reader = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIZE);
+    limits.max_image_height = Some(MAX_IMAGE_SIZE);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| e.to_string())?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let image = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    Ok(ctx.load_texture("page_image", image, Default::default()))
+}
+
+/// Asks the user for a path and writes `data` there, the file name defaults to the last segment of `url`
+fn save_to_file(url: &str, data: &[u8]) {
+    if let Some(path) = rfd::FileDialog::new()
+        .set_file_name(file_name(url))
+        .save_file()
+    {
+        if let Err(e) = std::fs::write(&path, data) {
+            remilog!("[SAVE ERROR] Couldn't write '{}': {e}", path.display());
+        }
+    }
+}
+
+/// Shows `add_contents` in a column of `width` centered in `ui`
+fn centered_column<R>(
+    ui: &mut egui::Ui,
+    width: f32,
+    add_contents: impl FnOnce(&mut egui::Ui) -> R,
+) -> R {
+    let margin = (ui.available_width() - width) / 2.;
+    ui.horizontal_top(|ui| {
+        ui.add_space(margin);
+        ui.vertical(|ui| {
+            ui.set_width(width);
+            add_contents(ui)
+        })
+        .inner
+    })
+    .inner
+}
+
+/// Whether text typed in the URL bar should be searched for rather than opened.
+/// Queries have no scheme and either contain spaces or are a single word without dots or slashes.
+fn is_search_query(input: &str) -> bool {
+    let input = input.trim();
+    if input.is_empty() || input.contains("://") {
+        return false;
+    }
+    input.contains(char::is_whitespace)
+        || !(input.contains(['.', '/', ':']) || input.eq_ignore_ascii_case("localhost"))
+}
+
+/// Whether the link `url` found on the page at `page_url` leaves the encrypted Gemini space for plain http
+fn is_insecure_link(page_url: &str, url: &str) -> bool {
+    url::scheme(page_url).is_some_and(|scheme| scheme == "gemini")
+        && url::scheme(url).is_some_and(|scheme| scheme == "http")
+}
+
+/// Absolute form of the link `url` found on the page at `base`, or `url` itself if it can't be resolved
+fn resolve_url(base: &str, url: &str) -> String {
+    let url = url.trim();
+    if url::scheme(url).is_some() {
+        return url.to_string();
+    }
+    GeminiUrl::parse(base)
+        .and_then(|base| base.join(&normalize_url(url)))
+        .map_or_else(|_| url.to_string(), |url| url.to_string())
+}
+
+/// Value of a setting to save, which is the stored one while it's still overridden by its flag
+fn saved_setting<'a, T: PartialEq>(current: &'a T, overridden: &'a Option<(T, T)>) -> &'a T {
+    match overridden {
+        Some((stored, flag)) if current == flag => stored,
+        _ => current,
+    }
+}
+
+/// Percent-encodes the characters of `url` that can't appear in a URL as they are
+/// (whitespace, non-ASCII and a few unsafe symbols), reserved characters and existing escapes are kept
+fn normalize_url(url: &str) -> String {
+    let mut res = String::with_capacity(url.len());
+    for c in url.chars() {
+        if c.is_ascii_graphic()
+            && !matches!(c, '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}')
+        {
+            res.push(c);
+        } else {
+            res.push_str(&percent_encode(c.encode_utf8(&mut [0; 4])));
+        }
+    }
+    res
+}
+
+/// Label for `text` with the matches of the current search highlighted, scrolled to if it holds the selected match
+fn find_label(
+    ui: &mut egui::Ui,
+    find: &mut Option<Find>,
+    text: &str,
+    size: f32,
+    color: Color32,
+) -> egui::Response {
+    let (text, scroll) = find::highlight(
+        find,
+        text,
+        FontId::proportional(size),
+        color,
+        Color32::TRANSPARENT,
+    );
+    let response = ui.label(text);
+    if scroll {
+        response.scroll_to_me(Some(egui::Align::Center));
+    }
+    response
+}
+
+/// Label for the text of the entry at `i`, see `entry_text`
+fn entry_label(
+    ui: &mut egui::Ui,
+    layout: &mut PageLayout,
+    i: usize,
+    find: &mut Option<Find>,
+    text: &str,
+    size: f32,
+    color: Color32,
+) -> egui::Response {
+    let format = TextFormat::simple(FontId::proportional(size), color);
+    let (text, scroll) = entry_text(ui, layout, i, find, text, format);
+    let response = ui.label(text);
+    if scroll {
+        response.scroll_to_me(Some(egui::Align::Center));
+    }
+    response
+}
+
+/// `text` of the entry at `i` with the matches of the current search highlighted, see `find::highlight`.
+/// Without any match, it's laid out once the way `egui::Label` would in `ui`, then kept in `layout`.
+fn entry_text(
+    ui: &egui::Ui,
+    layout: &mut PageLayout,
+    i: usize,
+    find: &mut Option<Find>,
+    text: &str,
+    format: TextFormat,
+) -> (egui::WidgetText, bool) {
+    if find
+        .as_ref()
+        .is_some_and(|find| !find.matches(text).is_empty())
+    {
+        let (mut text, scroll) =
+            find::highlight(find, text, format.font_id, format.color, format.background);
+        // Only the font and colors are passed on to the highlighted text
+        if let egui::WidgetText::LayoutJob(job) = &mut text {
+            for section in &mut job.sections {
+                section.format.italics = format.italics;
+            }
+        }
+        return (text, scroll);
+    }
+    let galley = layout.galley(i, || {
+        let mut text = RichText::new(text)
+            .font(format.font_id)
+            .color(format.color)
+            .background_color(format.background);
+        if format.italics {
+            text = text.italics();
+        }
+        let mut job = egui::WidgetText::from(text).into_layout_job(
+            ui.style(),
+            egui::FontSelection::Default,
+            ui.text_valign(),
+        );
+        job.wrap = egui::text::TextWrapping::from_wrap_mode_and_width(
+            ui.wrap_mode(),
+            ui.available_width(),
+        );
+        job.halign = ui.layout().horizontal_placement();
+        job.justify = ui.layout().horizontal_justify();
+        ui.fonts(|f| f.layout_job(job))
+    });
+    (galley.into(), false)
+}
+
+/// Number of matches of `find` that rendering `entry` would highlight
+fn entry_matches(find: &Find, entry: &gemtext::GemtextEntry, linkify_text: bool) -> usize {
+    match entry {
+        gemtext::GemtextEntry::Text(t) => match linkify_text.then(|| gemtext::linkify(t)).flatten()
+        {
+            Some(spans) => spans
+                .iter()
+                .map(|span| match span {
+                    gemtext::Span::Text(text) => find.matches(text).len(),
+                    gemtext::Span::Url(_) => 0,
+                })
+                .sum(),
+            None => find.matches(t).len(),
+        },
+        gemtext::GemtextEntry::Link { label, .. } => find.matches(label).len(),
+        gemtext::GemtextEntry::MinorHeading(text)
+        | gemtext::GemtextEntry::MediumHeading(text)
+        | gemtext::GemtextEntry::MajorHeading(text) => find.matches(text).len(),
+        gemtext::GemtextEntry::Quote(lines) => find.matches(&lines.join("\n")).len(),
+        gemtext::GemtextEntry::List(elems) => elems.iter().map(|el| find.matches(el).len()).sum(),
+        gemtext::GemtextEntry::Preformatted { body, .. } => find.matches(body).len(),
+    }
+}
+
+/// Preformatted block of the entry at `i`, under a header that folds it when clicked.
+/// Its lines don't wrap, they scroll horizontally over the block's background.
+#[allow(clippy::too_many_arguments)]
+fn preformatted_block(
+    ui: &mut egui::Ui,
+    layout: &mut PageLayout,
+    find: &mut Option<Find>,
+    i: usize,
+    alt_text: &str,
+    body: &str,
+    open: bool,
+    text_size: f32,
+    theme: Theme,
+) -> egui::CollapsingResponse<()> {
+    let title = if alt_text.is_empty() {
+        "preformatted"
+    } else {
+        alt_text
+    };
+    egui::CollapsingHeader::new(
+        RichText::new(title)
+            .size(text_size / 1.5)
+            .color(theme.alt_text)
+            .italics(),
+    )
+    .id_salt(i)
+    .open(Some(open))
+    .show_unindented(ui, |ui| {
+        ui.push_id(i, |ui| {
+            // Idea taken from egui::TextEdit::show
+            let where_to_put_background = ui.painter().add(egui::Shape::Noop);
+            let output = egui::ScrollArea::horizontal()
+                .auto_shrink([false, true])
+                .drag_to_scroll(false)
+                .min_scrolled_width(0.)
+                .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysVisible)
+                .show(ui, |ui| {
+                    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+                    let format =
+                        TextFormat::simple(FontId::monospace(text_size), theme.preformatted_text);
+                    let (text, scroll) = entry_text(ui, layout, i, find, body, format);
+                    let response = ui.add(egui::Label::new(text).selectable(true).extend());
+                    if scroll {
+                        response.scroll_to_me(Some(egui::Align::Center));
+                    }
+                    response
+                });
+            let rect = output.inner.rect.with_max_x(output.inner_rect.max.x);
+            ui.painter().set(
+                where_to_put_background,
+                egui::epaint::RectShape::filled(rect, 0, theme.preformatted_bg),
+            );
+            if !alt_text.is_empty() {
+                output.inner.on_hover_text(alt_text);
+            }
+        });
+    })
+}
+
+/// Text line with the URLs found by `gemtext::linkify` shown as links, flowing on as many rows as needed.
+/// `page_url` is the page the line is on. Returns the response of each URL.
+fn linkified_label<'a>(
+    ui: &mut egui::Ui,
+    find: &mut Option<Find>,
+    spans: &[gemtext::Span<'a>],
+    page_url: &str,
+    size: f32,
+    theme: Theme,
+) -> Vec<(&'a str, egui::Response)> {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.;
+        let mut links = vec![];
+        for span in spans {
+            match *span {
+                gemtext::Span::Text(text) => {
+                    find_label(ui, find, text, size, theme.text);
+                }
+                gemtext::Span::Url(url) => {
+                    let color = if url.starts_with("gemini://") {
+                        theme.link
+                    } else if is_insecure_link(page_url, url) {
+                        theme.insecure_link
+                    } else {
+                        theme.external_link
+                    };
+                    links.push((url, ui.link(RichText::new(url).size(size).color(color))));
+                }
+            }
+        }
+        links
+    })
+    .inner
+}
+
+/// Whether `fragment` names `heading`, either verbatim or through its slug
+fn heading_matches(heading: &str, fragment: &str) -> bool {
+    heading.trim().eq_ignore_ascii_case(fragment) || gemtext::slug(heading) == slug_case(fragment)
+}
+
+/// `fragment` lowercased the same way as slugs
+fn slug_case(fragment: &str) -> String {
+    fragment.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Whether `url` is a `host:port[/path]` without a scheme, like `localhost:1966/`.
+/// It would otherwise be read as a URL with the host as its scheme.
+/// Only hosts that can't be schemes are recognized, so that `mailto:123` stays a URL.
+fn is_host_port(url: &str) -> bool {
+    if url.contains("://") {
+        return false;
+    }
+    let authority = url.split(['/', '?', '#']).next().unwrap_or_default();
+    match split_host_port(authority) {
+        (host, Some(port)) => {
+            (host.contains('.')
+                || host.eq_ignore_ascii_case("localhost")
+                || authority.starts_with('['))
+                && !port.is_empty()
+                && port.chars().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+pub fn redirect(server_name: &mut String, request_data: &mut String, url: &str) -> bool {
+    let mut url = normalize_url(url.trim());
+    if is_host_port(&url) {
+        url.insert_str(0, "gemini://");
+    }
+    let url = &url;
+    // Internal pages are built by the browser, the server stays the same
+    if url::scheme(url).is_some_and(|scheme| scheme == "about") {
+        *request_data = url.to_ascii_lowercase();
+        return true;
+    }
+    let res = match GeminiUrl::parse(request_data) {
+        Ok(base) => base.join(url),
+        Err(_) => GeminiUrl::parse(url),
+    };
+    match res {
+        Ok(res) if matches!(res.scheme.as_str(), "gemini" | "titan" | "spartan") => {
+            *server_name = res.authority();
+            *request_data = res.to_string();
+            true
+        }
+        Ok(_) => {
+            remilog!("[REDIRECT ERROR] '{url}' contains unsupported protocol.");
+            false
+        }
+        Err(e) => {
+            remilog!("[REDIRECT ERROR] {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let (favicon_sender, favicon_receiver) = mpsc::channel();
+        App {
+            tabs: vec![],
+            active_tab: 0,
+            bookmarks: vec![],
+            subscriptions: vec![],
+            feed_updates: None,
+            favicons: HashMap::new(),
+            refresh_stats: false,
+            favicon_sender,
+            favicon_receiver,
+            editing_bookmarks: false,
+            new_folder_name: String::new(),
+            show_toc: false,
+            folded_blocks: HashSet::new(),
+            wrap_text: true,
+            link_number: String::new(),
+            known_hosts: Arc::new(Mutex::new(KnownHosts::default())),
+            identities: Identities::load(None),
+            identity_scope: CertScope::default(),
+            cache: Cache::load(None, 0),
+            settings: Settings::default(),
+            home_url_override: None,
+            verification_override: None,
+        }
+    }
+
+    /// Hands `raw` to `tab` as the response to its current request, instead of sending it
+    fn respond(app: &mut App, tab: &mut Tab, raw: &str) {
+        let ctx = egui::Context::default();
+        // What sending the request resets
+        tab.redir = false;
+        tab.retry_at = None;
+        tab.loading = Some(Loading::cached(raw.as_bytes().to_vec()));
+        while tab.loading.is_some() {
+            std::thread::yield_now();
+            app.update_loading(tab, &ctx);
+        }
+    }
+
+    #[test]
+    fn redirect_loop_is_stopped() {
+        let mut app = app();
+        let mut tab = Tab::open("gemini://example.org/a");
+        for i in 0..=MAX_REDIRECTS {
+            assert!(tab.redir, "redirection {i} wasn't followed");
+            let to = if i % 2 == 0 { "/b" } else { "/a" };
+            respond(&mut app, &mut tab, &format!("31 {to}\r\n"));
+        }
+        assert!(!tab.redir);
+        let Page::Error { title, msg, .. } = &tab.page else {
+            panic!("the loop didn't end on an error page");
+        };
+        assert_eq!(title, "Too many redirections");
+        assert!(msg.starts_with("gemini://example.org/a -> gemini://example.org/b -> "));
+        assert!(tab.redirects.is_empty());
+    }
+
+    #[test]
+    fn temporary_redirect_is_followed() {
+        let mut app = app();
+        // Keeps the favicon of the host from being requested
+        app.favicons.insert("example.org".to_string(), None);
+        let mut tab = Tab::open("gemini://example.org/a");
+        respond(&mut app, &mut tab, "30 /b\r\n");
+        assert!(tab.redir);
+        assert_eq!(tab.request_data, "gemini://example.org/b");
+        respond(&mut app, &mut tab, "20 text/gemini\r\n# B\n");
+        assert!(matches!(tab.page, Page::Gemtext(_)));
+        // The temporary target isn't the page's canonical URL
+        assert_eq!(
+            tab.history,
+            [(
+                "example.org".to_string(),
+                "gemini://example.org/a".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn search_query_classification() {
+        for (input, is_query) in [
+            ("example.org", false),
+            ("example.org/page.gmi", false),
+            ("localhost", false),
+            ("localhost:1966", false),
+            ("example.org:1966/", false),
+            ("gemini://example.org/", false),
+            ("gemini://example.org/a b", false),
+            ("about:settings", false),
+            ("gemini", true),
+            ("  rust  ", true),
+            ("gemini protocol", true),
+            ("what is gemini.org", true),
+            ("", false),
+        ] {
+            assert_eq!(is_search_query(input), is_query, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn host_port_without_scheme() {
+        for (input, host_port) in [
+            ("localhost:1966/", true),
+            ("localhost:1966", true),
+            ("example.org:1966/a?q", true),
+            ("127.0.0.1:1966", true),
+            ("[::1]:1966/", true),
+            ("mailto:123", false),
+            ("tel:5551234", false),
+            ("about:settings", false),
+            ("example.org/", false),
+            ("example.org:/", false),
+            ("gemini://example.org:1966/", false),
+        ] {
+            assert_eq!(is_host_port(input), host_port, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn not_found_on_first_navigation() {
+        let mut app = app();
+        let mut tab = Tab::open("gemini://example.org/missing");
+        respond(&mut app, &mut tab, "51 No such page\r\n");
+        let Page::Error { title, msg, .. } = &tab.page else {
+            panic!("the 51 didn't show an error page");
+        };
+        assert_eq!(title, "Not found");
+        assert!(msg.contains("gemini://example.org/missing"));
+        assert!(msg.contains("No such page"));
+        // There's no page to go back to
+        assert_eq!(tab.server_name, DEFAULT_SERVER);
+        assert_eq!(tab.request_data, DEFAULT_URL);
+        assert!(tab.history.is_empty());
+    }
+
+    fn history_urls(tab: &Tab) -> Vec<&str> {
+        tab.history.iter().map(|(_, url)| url.as_str()).collect()
+    }
+
+    #[test]
+    fn navigating_after_going_back_drops_forward_history() {
+        let mut app = app();
+        app.favicons.insert("example.org".to_string(), None);
+        let page = "20 text/gemini\r\n# Page\n";
+        let mut tab = Tab::open("gemini://example.org/a");
+        respond(&mut app, &mut tab, page);
+        for url in ["gemini://example.org/b", "gemini://example.org/c"] {
+            tab.navigate(url);
+            respond(&mut app, &mut tab, page);
+        }
+        assert_eq!(tab.history_index, 2);
+        tab.go_back();
+        // What `update_loading` does before sending the request
+        (tab.server_name, tab.request_data) = tab.history[tab.history_index].clone();
+        respond(&mut app, &mut tab, page);
+        assert_eq!(tab.history_index, 1);
+        assert_eq!(history_urls(&tab).len(), 3);
+
+        tab.navigate("gemini://example.org/d");
+        respond(&mut app, &mut tab, page);
+        assert_eq!(
+            history_urls(&tab),
+            [
+                "gemini://example.org/a",
+                "gemini://example.org/b",
+                "gemini://example.org/d"
+            ]
+        );
+        assert_eq!(tab.history_index, 2);
+        assert_eq!(tab.loaded_history_index, 2);
+    }
+
+    #[test]
+    fn navigating_to_current_url_keeps_history() {
+        let mut app = app();
+        app.favicons.insert("example.org".to_string(), None);
+        let page = "20 text/gemini\r\n# Page\n";
+        let mut tab = Tab::open("gemini://example.org/a");
+        respond(&mut app, &mut tab, page);
+        tab.navigate("gemini://example.org/b");
+        respond(&mut app, &mut tab, page);
+        tab.go_back();
+        (tab.server_name, tab.request_data) = tab.history[tab.history_index].clone();
+        respond(&mut app, &mut tab, page);
+
+        tab.navigate("gemini://example.org/a");
+        respond(&mut app, &mut tab, page);
+        assert_eq!(
+            history_urls(&tab),
+            ["gemini://example.org/a", "gemini://example.org/b"]
+        );
+        assert_eq!(tab.history_index, 0);
+    }
+
+    #[test]
+    fn go_back_from_failed_history_move() {
+        let mut app = app();
+        app.favicons.insert("example.org".to_string(), None);
+        let page = "20 text/gemini\r\n# Page\n";
+        let mut tab = Tab::open("gemini://example.org/a");
+        respond(&mut app, &mut tab, page);
+        for url in ["gemini://example.org/b", "gemini://example.org/c"] {
+            tab.navigate(url);
+            respond(&mut app, &mut tab, page);
+        }
+        for failure in ["42 Script failed\r\n", "44 30\r\n"] {
+            tab.go_back();
+            (tab.server_name, tab.request_data) = tab.history[tab.history_index].clone();
+            respond(&mut app, &mut tab, failure);
+            assert!(matches!(tab.page, Page::Error { .. }));
+            assert_eq!(tab.history_index, 1);
+
+            // The page that was shown before the error is requested again
+            tab.leave_error();
+            assert!(tab.redir);
+            assert_eq!(tab.request_data, "gemini://example.org/c");
+            assert_eq!(tab.history_index, 2);
+            respond(&mut app, &mut tab, page);
+            assert_eq!(
+                history_urls(&tab),
+                [
+                    "gemini://example.org/a",
+                    "gemini://example.org/b",
+                    "gemini://example.org/c"
+                ]
+            );
+            assert_eq!(tab.history_index, 2);
+        }
+    }
+
+    #[test]
+    fn page_generation_changes_with_the_page() {
+        let mut app = app();
+        app.favicons.insert("example.org".to_string(), None);
+        let mut tab = Tab::open("gemini://example.org/a");
+        respond(&mut app, &mut tab, "20 text/gemini\r\n# A\n");
+        let generation = tab.page_generation;
+        // Reloading the same page keeps its layout
+        tab.navigate("gemini://example.org/a");
+        respond(&mut app, &mut tab, "20 text/gemini\r\n# A\n");
+        assert_eq!(tab.page_generation, generation);
+        // Pages with as many entries don't share theirs
+        tab.navigate("gemini://example.org/b");
+        respond(&mut app, &mut tab, "20 text/gemini\r\n# B\n");
+        assert_ne!(tab.page_generation, generation);
     }
 }
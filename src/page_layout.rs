@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+use std::sync::Arc;
+
+use eframe::egui::Galley;
+use remi::gemtext::GemtextEntry;
+
+use crate::theme::Theme;
+
+/// What the layout of the entries depends on, they are laid out again when any of it changes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayoutKey {
+    /// Generation of the page in its tab, which changes whenever another page is shown
+    pub page: u64,
+    pub entries: usize,
+    pub width: f32,
+    pub text_size: f32,
+    pub theme: Theme,
+    pub right_to_left: bool,
+    pub wrap_text: bool,
+    pub linkify_text: bool,
+}
+
+/// Heights of a row of each kind of text, the entries that weren't rendered yet are estimated from them
+#[derive(Debug, Clone, Copy)]
+pub struct RowHeights {
+    pub text: f32,
+    pub monospace: f32,
+    pub minor_heading: f32,
+    pub medium_heading: f32,
+    pub major_heading: f32,
+    /// Space left after every widget
+    pub spacing: f32,
+}
+
+/// Heights of the Gemtext entries of a page as they were last rendered,
+/// so that only the entries in view have to be laid out.
+/// The text of the entries shown so far is kept laid out, scrolling back to them doesn't shape it again.
+#[derive(Debug, Default)]
+pub struct PageLayout {
+    key: LayoutKey,
+    heights: Vec<Option<f32>>,
+    galleys: Vec<Option<Arc<Galley>>>,
+}
+
+impl PageLayout {
+    /// Forgets the measured heights and the laid out text if `key` changed
+    pub fn update(&mut self, key: LayoutKey) {
+        if key != self.key {
+            self.key = key;
+            self.heights = vec![None; key.entries];
+            self.galleys = vec![None; key.entries];
+        }
+    }
+
+    /// Height of the entry at `i`, spacing included, estimated if it was never rendered
+    pub fn height(&self, i: usize, entry: &GemtextEntry, rows: RowHeights) -> f32 {
+        match self.heights.get(i) {
+            Some(Some(height)) => *height,
+            _ => estimate_height(entry, rows),
+        }
+    }
+
+    pub fn set_height(&mut self, i: usize, height: f32) {
+        if let Some(h) = self.heights.get_mut(i) {
+            *h = Some(height);
+        }
+    }
+
+    /// Text of the entry at `i`, laid out with `layout` the first time it's needed
+    pub fn galley(&mut self, i: usize, layout: impl FnOnce() -> Arc<Galley>) -> Arc<Galley> {
+        match self.galleys.get_mut(i) {
+            Some(galley) => galley.get_or_insert_with(layout).clone(),
+            None => layout(),
+        }
+    }
+}
+
+/// Height of `entry` when none of its lines wrap
+fn estimate_height(entry: &GemtextEntry, rows: RowHeights) -> f32 {
+    let lines = |text: &str| text.lines().count().max(1) as f32;
+    match entry {
+        GemtextEntry::Text(_) | GemtextEntry::Link { .. } => rows.text + rows.spacing,
+        GemtextEntry::MinorHeading(_) => rows.minor_heading + rows.spacing,
+        GemtextEntry::MediumHeading(_) => rows.medium_heading + rows.spacing,
+        GemtextEntry::MajorHeading(_) => rows.major_heading + rows.spacing,
+        GemtextEntry::List(elems) => elems.len() as f32 * (rows.text + rows.spacing),
+        GemtextEntry::Quote(q) => q.len() as f32 * rows.text + rows.spacing,
+        // Unfolded, below the header showing the alt text
+        GemtextEntry::Preformatted { body, .. } => {
+            rows.text / 1.5 + rows.spacing + lines(body) * rows.monospace + rows.spacing
+        }
+    }
+}
@@ -0,0 +1,114 @@
+#![allow(dead_code)]
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    identity::ClientIdentity,
+    request::{connect, read_limited, request, CancelHandle, RequestOptions, RequestResult},
+    response::GeminiResponse,
+    url::{self, percent_decode, split_host_port, GeminiUrl},
+    verifier::KnownHosts,
+};
+
+/// Port connected to when a Spartan URL doesn't include one
+pub const SPARTAN_DEFAULT_PORT: u16 = 300;
+
+/// Protocol a URL is requested with, picked from its scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Gemini,
+    /// Plain TCP sibling of Gemini, only used when `RequestOptions::spartan` is set
+    Spartan,
+}
+
+impl Protocol {
+    pub fn from_url(url: &str) -> Self {
+        match url::scheme(url).as_deref() {
+            Some("spartan") => Self::Spartan,
+            _ => Self::Gemini,
+        }
+    }
+
+    /// Requests `url` from `server_name` and parses the response, which is returned along with its raw bytes.
+    /// Spartan responses are returned as the equivalent Gemini ones, see `spartan_to_gemini`.
+    pub fn request(
+        self,
+        server_name: &str,
+        url: &str,
+        known_hosts: &Arc<Mutex<KnownHosts>>,
+        identity: Option<&ClientIdentity>,
+        options: RequestOptions,
+        cancel: &CancelHandle,
+    ) -> RequestResult<(GeminiResponse, Vec<u8>)> {
+        match self {
+            Self::Gemini => request(
+                server_name,
+                url.as_bytes(),
+                known_hosts,
+                identity,
+                options,
+                cancel,
+            ),
+            Self::Spartan if !options.spartan => {
+                Err("Spartan support is turned off in the settings".into())
+            }
+            Self::Spartan => {
+                let raw = spartan_to_gemini(spartan_request(server_name, url, options, cancel)?)?;
+                Ok((GeminiResponse::from_bytes(&raw)?, raw))
+            }
+        }
+    }
+}
+
+/// Sends the `host path length` request line for `url` over plain TCP, followed by its decoded query as data
+fn spartan_request(
+    server_name: &str,
+    url: &str,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<Vec<u8>> {
+    let url = GeminiUrl::parse(url)?;
+    let path = format!("/{}", url.path.join("/"));
+    let data = url.query.as_deref().map(percent_decode).unwrap_or_default();
+    let (host, port) = split_host_port(server_name);
+    let port = match port {
+        Some(port) => port.parse()?,
+        None => SPARTAN_DEFAULT_PORT,
+    };
+    let mut sock = connect(host, port, options.connect_timeout)?;
+    cancel.register(&sock)?;
+    sock.set_read_timeout(Some(options.read_timeout))?;
+    sock.set_write_timeout(Some(options.read_timeout))?;
+    sock.write_all(format!("{host} {path} {}\r\n", data.len()).as_bytes())?;
+    sock.write_all(data.as_bytes())?;
+    read_limited(&mut sock, options.max_response_size)
+}
+
+/// Rewrites the header of a Spartan response as the equivalent Gemini one, the body is kept as it is.
+/// Spartan statuses are a single digit: 2 success, 3 redirect, 4 client error and 5 server error.
+fn spartan_to_gemini(raw: Vec<u8>) -> RequestResult<Vec<u8>> {
+    let header_len = raw
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .ok_or("Spartan response has no header")?;
+    let code: &[u8] = match raw[..header_len] {
+        [b'2', b' ', ..] => b"20",
+        // Redirects point at a path on the same host, which the relative URL resolution handles
+        [b'3', b' ', ..] => b"30",
+        [b'4', b' ', ..] => b"59",
+        [b'5', b' ', ..] => b"40",
+        _ => {
+            return Err(format!(
+                "invalid Spartan header '{}'",
+                String::from_utf8_lossy(&raw[..header_len])
+            )
+            .into())
+        }
+    };
+    let mut res = Vec::with_capacity(raw.len() + 1);
+    res.extend_from_slice(code);
+    res.extend_from_slice(&raw[1..]);
+    Ok(res)
+}
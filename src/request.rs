@@ -0,0 +1,518 @@
+#![allow(dead_code)]
+use std::{
+    error::Error,
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rustls::{pki_types::ServerName, RootCertStore};
+
+use crate::{
+    identity::ClientIdentity,
+    protocol::Protocol,
+    response::{GeminiResponse, GeminiResponseParseError, ResponseHeader},
+    url::{self, percent_encode, split_host_port, GeminiUrl},
+    verifier::{self, GeminiCertVerifier, KnownHosts, VerificationMode},
+};
+
+pub type RequestResult<T> = Result<T, RequestError>;
+
+/// Why a request failed, telling apart the steps of a request so that each can be explained
+#[derive(Debug)]
+pub enum RequestError {
+    /// The host name couldn't be resolved
+    Dns(std::io::Error),
+    /// The connection couldn't be opened, or was lost
+    Connect(std::io::Error),
+    /// The TLS handshake failed, including when the certificate was rejected
+    Tls(rustls::Error),
+    Timeout,
+    /// The request couldn't be built or the response isn't valid Gemini
+    Protocol(Box<dyn Error + Send + Sync>),
+    /// The request or the response is over the limit of `RequestOptions`
+    TooLarge(String),
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dns(e) => write!(f, "couldn't resolve host: {e}"),
+            Self::Connect(e) => write!(f, "couldn't connect: {e}"),
+            Self::Tls(e) => write!(f, "TLS handshake failed: {e}"),
+            Self::Timeout => write!(f, "the server didn't respond in time"),
+            Self::Protocol(e) => write!(f, "{e}"),
+            Self::TooLarge(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl Error for RequestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Dns(e) | Self::Connect(e) => Some(e),
+            Self::Tls(e) => Some(e),
+            Self::Protocol(e) => Some(e.as_ref()),
+            Self::Timeout | Self::TooLarge(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RequestError {
+    /// Errors of the TLS layer are wrapped in IO errors by the stream
+    fn from(e: std::io::Error) -> Self {
+        if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) {
+            return Self::Timeout;
+        }
+        if e.get_ref().is_some_and(|inner| inner.is::<rustls::Error>()) {
+            let inner = e.into_inner().unwrap().downcast::<rustls::Error>().unwrap();
+            return Self::Tls(*inner);
+        }
+        Self::Connect(e)
+    }
+}
+
+impl From<rustls::Error> for RequestError {
+    fn from(e: rustls::Error) -> Self {
+        Self::Tls(e)
+    }
+}
+
+/// Errors about the content of the request or the response
+macro_rules! protocol_errors {
+    ($($error:ty),*) => {
+        $(impl From<$error> for RequestError {
+            fn from(e: $error) -> Self {
+                Self::Protocol(Box::new(e))
+            }
+        })*
+    };
+}
+
+protocol_errors!(
+    GeminiResponseParseError,
+    url::UrlParseError,
+    std::num::ParseIntError,
+    std::str::Utf8Error,
+    rustls::pki_types::InvalidDnsNameError
+);
+
+impl From<Box<dyn Error + Send + Sync>> for RequestError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        Self::Protocol(e)
+    }
+}
+
+impl From<&str> for RequestError {
+    fn from(msg: &str) -> Self {
+        Self::Protocol(msg.into())
+    }
+}
+
+impl From<String> for RequestError {
+    fn from(msg: String) -> Self {
+        Self::Protocol(msg.into())
+    }
+}
+
+type TlsStream = rustls::StreamOwned<rustls::ClientConnection, TcpStream>;
+
+/// Lets another thread abort a request by shutting its socket down
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<Mutex<CancelState>>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: bool,
+    sock: Option<TcpStream>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.cancelled = true;
+        if let Some(sock) = state.sock.take() {
+            let _ = sock.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub(crate) fn register(&self, sock: &TcpStream) -> std::io::Result<()> {
+        let mut state = self.0.lock().unwrap();
+        if state.cancelled {
+            return Err(std::io::Error::new(
+                ErrorKind::Interrupted,
+                "request was cancelled",
+            ));
+        }
+        state.sock = Some(sock.try_clone()?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RequestOptions {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    /// Responses larger than this many bytes are rejected
+    pub max_response_size: usize,
+    /// Requests longer than this many bytes, CRLF included, are rejected before being sent
+    pub max_request_size: usize,
+    /// Port connected to when the URL doesn't include one
+    pub default_port: u16,
+    pub verification: VerificationMode,
+    /// Whether spartan:// URLs can be requested, see `Protocol`
+    pub spartan: bool,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(15),
+            max_response_size: 5 * 1024 * 1024,
+            // The limit of Gemini URLs
+            max_request_size: 1024,
+            default_port: 1965,
+            verification: VerificationMode::default(),
+            spartan: false,
+        }
+    }
+}
+
+/// Clears the certificate mismatch left by a request to `server_name` made outside of a tab,
+/// certificate changes are only confirmed when loading pages
+pub fn forget_mismatch(known_hosts: &Mutex<KnownHosts>, server_name: &str) {
+    let host = split_host_port(server_name).0;
+    known_hosts
+        .lock()
+        .unwrap()
+        .mismatch
+        .take_if(|m| m.host == host);
+}
+
+/// Requests the absolute `url` without a client certificate, the simplest way to make a request.
+/// Certificates are trusted on first use by a `KnownHosts` that only lives for this request.
+/// The response is returned along with its raw bytes.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use remi::{fetch, GeminiResponse, RequestOptions};
+///
+/// let options = RequestOptions {
+///     connect_timeout: Duration::from_secs(5),
+///     ..RequestOptions::default()
+/// };
+/// let (response, _raw) = fetch("gemini://geminiprotocol.net/", options)?;
+/// if let GeminiResponse::Redirection { to, .. } = response {
+///     println!("redirected to {to}");
+/// }
+/// # Ok::<(), remi::RequestError>(())
+/// ```
+pub fn fetch(url: &str, options: RequestOptions) -> RequestResult<(GeminiResponse, Vec<u8>)> {
+    let server_name = GeminiUrl::parse(url)?.authority();
+    Protocol::from_url(url).request(
+        &server_name,
+        url,
+        &Arc::new(Mutex::new(KnownHosts::default())),
+        None,
+        options,
+        &CancelHandle::default(),
+    )
+}
+
+/// Sends the request and parses the response, which is returned along with its raw bytes
+pub fn request(
+    server_name: &str,
+    data: &[u8],
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<(GeminiResponse, Vec<u8>)> {
+    let recv = request_raw(server_name, data, known_hosts, identity, options, cancel)?;
+    Ok((GeminiResponse::from_bytes(&recv)?, recv))
+}
+
+pub fn request_raw(
+    server_name: &str,
+    data: &[u8],
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<Vec<u8>> {
+    let mut tls = send_request(server_name, data, known_hosts, identity, options, cancel)?;
+    read_limited(&mut tls, options.max_response_size)
+}
+
+/// Sends `body` to the Titan URL `url` and parses the response, which is a regular Gemini response.
+/// The size and the optional `token` are added to the path as Titan parameters.
+#[allow(clippy::too_many_arguments)]
+pub fn upload(
+    server_name: &str,
+    url: &str,
+    body: &[u8],
+    token: &str,
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<(GeminiResponse, Vec<u8>)> {
+    let mut url = GeminiUrl::parse(url)?;
+    url.fragment = None;
+    if url.path.is_empty() {
+        url.path.push(String::new());
+    }
+    let mut params = format!(";size={}", body.len());
+    if !token.trim().is_empty() {
+        params.push_str(&format!(";token={}", percent_encode(token.trim())));
+    }
+    url.path.last_mut().unwrap().push_str(&params);
+
+    let line = format!("{url}\r\n");
+    check_request_size(line.len(), options)?;
+    let mut tls = connect_tls(server_name, known_hosts, identity, options, cancel)?;
+    tls.write_all(line.as_bytes())?;
+    tls.write_all(body)?;
+    let recv = read_limited(&mut tls, options.max_response_size)?;
+    Ok((GeminiResponse::from_bytes(&recv)?, recv))
+}
+
+/// Fetches `data` and writes the body of the response to `path` as it arrives.
+/// Only successful responses are saved, returns the number of bytes written.
+pub fn download(
+    server_name: &str,
+    data: &[u8],
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+    path: &Path,
+) -> RequestResult<u64> {
+    let mut tls = send_request(server_name, data, known_hosts, identity, options, cancel)?;
+    let header = read_header(&mut tls)?;
+    let header = String::from_utf8_lossy(&header);
+    let header = header.trim_end();
+    if !header.starts_with('2') {
+        return Err(format!("server answered '{header}'").into());
+    }
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let written = std::io::copy(&mut tls, &mut file)?;
+    file.flush()?;
+    Ok(written)
+}
+
+/// Sends the request for `data` and only reads the header of the response, the body is never received
+pub fn request_header(
+    server_name: &str,
+    data: &[u8],
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<ResponseHeader> {
+    let mut tls = send_request(server_name, data, known_hosts, identity, options, cancel)?;
+    let header = read_header(&mut tls)?;
+    ResponseHeader::from_bytes(&header).ok_or_else(|| "invalid response header".into())
+}
+
+/// Reads the header line of a response, CRLF included
+fn read_header(reader: &mut impl Read) -> RequestResult<Vec<u8>> {
+    // The header is at most a two digit status, a space, 1024 bytes of meta and CRLF
+    let mut header = vec![];
+    let mut byte = [0];
+    while !header.ends_with(b"\r\n") {
+        if header.len() > 1029 {
+            return Err("response header is too long".into());
+        }
+        reader.read_exact(&mut byte)?;
+        header.push(byte[0]);
+    }
+    Ok(header)
+}
+
+/// Opens a TLS connection to `server_name` and sends the request line for `data`
+fn send_request(
+    server_name: &str,
+    data: &[u8],
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<TlsStream> {
+    let data = std::str::from_utf8(data)?.trim_end();
+    // Fragments are only meaningful to the client
+    let mut data = data
+        .split_once('#')
+        .map_or(data, |(data, _)| data)
+        .to_string();
+    data.push('\r');
+    data.push('\n');
+    check_request_size(data.len(), options)?;
+    let mut tls = connect_tls(server_name, known_hosts, identity, options, cancel)?;
+    tls.write_all(data.as_bytes())?;
+    Ok(tls)
+}
+
+/// Fails if a request line of `len` bytes is over the limit of `options`, servers would reject it anyway
+fn check_request_size(len: usize, options: RequestOptions) -> RequestResult<()> {
+    if len > options.max_request_size {
+        return Err(RequestError::TooLarge(format!(
+            "the request is {len} bytes long, over the limit of {} bytes",
+            options.max_request_size
+        )));
+    }
+    Ok(())
+}
+
+/// Opens a TLS connection to `server_name`, on the default port of `options` unless it includes one
+fn connect_tls(
+    server_name: &str,
+    known_hosts: &Arc<Mutex<KnownHosts>>,
+    identity: Option<&ClientIdentity>,
+    options: RequestOptions,
+    cancel: &CancelHandle,
+) -> RequestResult<TlsStream> {
+    let roots = match options.verification {
+        VerificationMode::Strict => verifier::system_roots(),
+        VerificationMode::Tofu | VerificationMode::TrustAll => Arc::new(RootCertStore::empty()),
+    };
+    let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+    let mut config = if let Some(identity) = identity {
+        let (certs, key) = identity.parse()?;
+        builder.with_client_auth_cert(certs, key)?
+    } else {
+        builder.with_no_client_auth()
+    };
+    // Strict mode keeps the default verifier, which checks the chain against `roots`
+    if options.verification != VerificationMode::Strict {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(GeminiCertVerifier {
+                known_hosts: known_hosts.clone(),
+                trust_all: options.verification == VerificationMode::TrustAll,
+            }));
+    }
+    let (server_name, port) = split_host_port(server_name);
+    let port = match port {
+        Some(port) => port.parse()?,
+        None => options.default_port,
+    };
+    let conn = rustls::ClientConnection::new(
+        Arc::new(config),
+        ServerName::try_from(server_name.to_string())?,
+    )?;
+    let sock = connect(server_name, port, options.connect_timeout)?;
+    cancel.register(&sock)?;
+    sock.set_read_timeout(Some(options.read_timeout))?;
+    sock.set_write_timeout(Some(options.read_timeout))?;
+
+    Ok(rustls::StreamOwned::new(conn, sock))
+}
+
+/// Reads `reader` to the end, failing once more than `limit` bytes were received
+pub(crate) fn read_limited(reader: &mut impl Read, limit: usize) -> RequestResult<Vec<u8>> {
+    let mut recv = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut recv)?;
+    if recv.len() > limit {
+        return Err(RequestError::TooLarge(format!(
+            "response is larger than the limit of {limit} bytes"
+        )));
+    }
+    Ok(recv)
+}
+
+/// Tries every address `host` resolves to until one of them accepts the connection within `timeout`
+pub(crate) fn connect(host: &str, port: u16, timeout: Duration) -> RequestResult<TcpStream> {
+    let addrs: Vec<_> = (host, port)
+        .to_socket_addrs()
+        .map_err(RequestError::Dns)?
+        .collect();
+    let mut last_err = RequestError::Dns(std::io::Error::new(
+        ErrorKind::NotFound,
+        "host didn't resolve to any address",
+    ));
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(sock) => return Ok(sock),
+            Err(e) => last_err = e.into(),
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_limited_rejects_oversized_response() {
+        let body = vec![b'a'; 1000];
+        assert_eq!(read_limited(&mut &body[..], 1000).unwrap().len(), 1000);
+        assert!(matches!(
+            read_limited(&mut &body[..], 999),
+            Err(RequestError::TooLarge(_))
+        ));
+        // A server that never stops sending is cut off at the limit
+        assert!(matches!(
+            read_limited(&mut std::io::repeat(b'a'), 5 * 1024 * 1024),
+            Err(RequestError::TooLarge(_))
+        ));
+    }
+
+    /// URL of exactly `len` bytes
+    fn url_of_len(scheme: &str, len: usize) -> String {
+        let url = format!("{scheme}://example.org/");
+        format!("{url}{}", "a".repeat(len - url.len()))
+    }
+
+    #[test]
+    fn request_size_limit() {
+        let options = RequestOptions::default();
+        assert!(check_request_size(1024, options).is_ok());
+        assert!(matches!(
+            check_request_size(1025, options),
+            Err(RequestError::TooLarge(_))
+        ));
+        let options = RequestOptions {
+            max_request_size: 2048,
+            ..options
+        };
+        assert!(check_request_size(2048, options).is_ok());
+        assert!(check_request_size(2049, options).is_err());
+    }
+
+    #[test]
+    fn long_requests_fail_before_connecting() {
+        let known_hosts = Arc::new(Mutex::new(KnownHosts::default()));
+        let options = RequestOptions::default();
+        let cancel = CancelHandle::default();
+        // With its CRLF, the request line is 1025 bytes long
+        let url = url_of_len("gemini", 1023);
+        let res = request_raw(
+            "example.org",
+            url.as_bytes(),
+            &known_hosts,
+            None,
+            options,
+            &cancel,
+        );
+        assert!(matches!(res, Err(RequestError::TooLarge(_))));
+        // Titan parameters count too
+        let url = url_of_len("titan", 1023 - ";size=4".len());
+        let res = upload(
+            "example.org",
+            &url,
+            b"body",
+            "",
+            &known_hosts,
+            None,
+            options,
+            &cancel,
+        );
+        assert!(matches!(res, Err(RequestError::TooLarge(_))));
+    }
+}
@@ -1,163 +1,351 @@
 #![allow(dead_code)]
+use encoding_rs::{Encoding, UTF_8};
 use std::error::Error;
 
 #[derive(Debug)]
 pub enum GeminiResponse {
     Input {
+        /// Exact status code of the response
+        code: u8,
         kind: InputKind,
         prompt: String,
     },
     Success {
-        body: String,
+        code: u8,
+        meta: SuccessMeta,
+        body: SuccessBody,
     },
     Redirection {
+        code: u8,
         kind: RedirectionKind,
         to: String,
     },
     TemporaryFailure {
+        code: u8,
         kind: TemporaryFailureKind,
         msg: String,
     },
     PermanentFailure {
+        code: u8,
         kind: PermanentFailureKind,
         msg: String,
     },
     ClientCertificate {
+        code: u8,
         kind: CertificateErrorKind,
         msg: String,
     },
 }
 
+/// Parsed meta of a success response, e.g. `text/gemini; charset=utf-8; lang=en`
 #[derive(Debug)]
-pub struct GeminiResponseParseError {}
+pub struct SuccessMeta {
+    pub mime: String,
+    pub charset: Option<String>,
+    pub lang: Option<String>,
+}
+
+impl SuccessMeta {
+    pub fn parse(meta: &str) -> Self {
+        let mut params = meta.split(';');
+        let mime = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let mut res = Self {
+            // an empty meta defaults to gemtext as per the spec
+            mime: if mime.is_empty() {
+                "text/gemini".to_string()
+            } else {
+                mime
+            },
+            charset: None,
+            lang: None,
+        };
+        for param in params {
+            if let Some((key, value)) = param.split_once('=') {
+                let value = value.trim().trim_matches('"').to_string();
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "charset" => res.charset = Some(value.to_ascii_lowercase()),
+                    "lang" => res.lang = Some(value),
+                    _ => (),
+                }
+            }
+        }
+        res
+    }
+
+    /// Decodes `body` with the declared charset, defaulting to UTF-8 when it's missing or unknown.
+    /// Returns `None` if `body` isn't valid in that charset.
+    pub fn decode(&self, body: &[u8]) -> Option<String> {
+        let encoding = self
+            .charset
+            .as_deref()
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .unwrap_or(UTF_8);
+        encoding
+            .decode_without_bom_handling_and_without_replacement(body)
+            .map(|s| s.into_owned())
+    }
+}
+
+/// Whether `lang`, a `lang` parameter like `he` or `ar-EG`, is written right to left.
+/// Only the first language of a comma separated list is considered.
+pub fn is_rtl(lang: &str) -> bool {
+    const RTL_LANGUAGES: [&str; 14] = [
+        "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ji", "ps", "sd", "syr", "ug", "ur", "yi",
+    ];
+    const RTL_SCRIPTS: [&str; 6] = ["arab", "hebr", "syrc", "thaa", "nkoo", "adlm"];
+    let lang = lang.split(',').next().unwrap_or_default().trim();
+    let mut subtags = lang.split(['-', '_']);
+    let primary = subtags.next().unwrap_or_default();
+    // A script subtag overrides the usual script of the language, e.g. `az-Arab`
+    match subtags
+        .find(|subtag| subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+    {
+        Some(script) => RTL_SCRIPTS.contains(&script.to_ascii_lowercase().as_str()),
+        None => RTL_LANGUAGES.contains(&primary.to_ascii_lowercase().as_str()),
+    }
+}
+
+/// `bytes` as lines of 16 hexadecimal bytes, with their offset and printable ASCII characters
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut lines = vec![];
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<_> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(format!("{:04x}  {:<47}  |{ascii}|", i * 16, hex.join(" ")));
+    }
+    lines.join("\n")
+}
+
+/// Body of a success response, only `text/*` bodies are decoded
+#[derive(Debug)]
+pub enum SuccessBody {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Status line of a response, `<code> <meta>`
+#[derive(Debug, Clone)]
+pub struct ResponseHeader {
+    pub code: u8,
+    pub meta: String,
+}
+
+impl ResponseHeader {
+    /// Parses the header line at the start of a raw response
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let end = bytes.windows(2).position(|w| w == b"\r\n")?;
+        let line = std::str::from_utf8(&bytes[..end]).ok()?;
+        let code = line.get(..2)?.parse().ok()?;
+        let meta = line.get(2..).unwrap_or_default().trim().to_string();
+        Some(Self { code, meta })
+    }
+
+    /// Name of the class of the status code
+    pub fn class(&self) -> &'static str {
+        match self.code / 10 {
+            1 => "Input",
+            2 => "Success",
+            3 => "Redirect",
+            4 => "Temporary failure",
+            5 => "Permanent failure",
+            6 => "Client certificate required",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// Number of bytes of an invalid response kept in `GeminiResponseParseError`
+const PREVIEW_LEN: usize = 256;
+
+/// What made a response invalid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorReason {
+    /// Fewer bytes than a status code and the space following it
+    TooShort,
+    /// The status code isn't two ASCII digits
+    BadStatus,
+    /// The status code isn't followed by a space
+    MissingSpace,
+    /// The header doesn't end with CRLF
+    MissingCrlf,
+    InvalidUtf8Meta,
+    /// The first digit of the status code isn't one of the defined classes
+    UnknownStatusClass,
+    /// The body isn't valid in the charset of its `text/*` type
+    InvalidBody,
+}
+
+impl std::fmt::Display for ParseErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ParseErrorReason::TooShort => "the response is too short to hold a status code",
+            ParseErrorReason::BadStatus => "the status code isn't a two digit number",
+            ParseErrorReason::MissingSpace => "the status code isn't followed by a space",
+            ParseErrorReason::MissingCrlf => "the header doesn't end with CRLF",
+            ParseErrorReason::InvalidUtf8Meta => "the meta isn't valid UTF-8",
+            ParseErrorReason::UnknownStatusClass => "the status code has an unknown class",
+            ParseErrorReason::InvalidBody => "the body isn't valid in its charset",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+#[derive(Debug)]
+pub struct GeminiResponseParseError {
+    pub reason: ParseErrorReason,
+    /// Number of bytes that were received
+    pub len: usize,
+    /// First bytes of the response, at most `PREVIEW_LEN`
+    pub head: Vec<u8>,
+}
+
+impl GeminiResponseParseError {
+    pub fn new(bytes: &[u8], reason: ParseErrorReason) -> Self {
+        Self {
+            reason,
+            len: bytes.len(),
+            head: bytes[..bytes.len().min(PREVIEW_LEN)].to_vec(),
+        }
+    }
+}
+
 impl std::fmt::Display for GeminiResponseParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Couldn't parse gemini response")
+        write!(
+            f,
+            "Couldn't parse gemini response of {} bytes, {}",
+            self.len, self.reason
+        )
     }
 }
 
 impl Error for GeminiResponseParseError {}
 
 impl GeminiResponse {
+    /// Two digit status code of the response
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Input { code, .. }
+            | Self::Success { code, .. }
+            | Self::Redirection { code, .. }
+            | Self::TemporaryFailure { code, .. }
+            | Self::PermanentFailure { code, .. }
+            | Self::ClientCertificate { code, .. } => *code,
+        }
+    }
+
+    /// Parses a raw response, header line included
+    ///
+    /// ```
+    /// use remi::response::{GeminiResponse, SuccessBody};
+    ///
+    /// let response = GeminiResponse::from_bytes(b"20 text/gemini\r\n# Hello\n").unwrap();
+    /// assert_eq!(response.code(), 20);
+    /// assert!(matches!(
+    ///     response,
+    ///     GeminiResponse::Success { body: SuccessBody::Text(_), .. }
+    /// ));
+    /// ```
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, GeminiResponseParseError> {
-        let err = Err(GeminiResponseParseError {});
-        let mut crlf = false;
-        if bytes.len() < 2 {
-            return err;
+        let err = |reason| Err(GeminiResponseParseError::new(bytes, reason));
+        if bytes.len() < 3 {
+            return err(ParseErrorReason::TooShort);
         }
-        let code = if let Ok(c) = std::str::from_utf8(&bytes[..2]) {
-            if let Ok(c) = c.parse::<i32>() {
-                c
-            } else {
-                return err;
-            }
-        } else {
-            return err;
+        // `<digit><digit><space>`, a parsed integer could also be signed or a single digit
+        if !bytes[..2].iter().all(u8::is_ascii_digit) {
+            return err(ParseErrorReason::BadStatus);
+        }
+        if bytes[2] != b' ' {
+            return err(ParseErrorReason::MissingSpace);
+        }
+        let code = (bytes[0] - b'0') * 10 + (bytes[1] - b'0');
+        // The header ends at the first CR, which has to start a CRLF.
+        // Everything after it is the body, even if it starts with another CRLF.
+        let Some(cr) = bytes[3..].iter().position(|&b| b == b'\r').map(|i| i + 3) else {
+            return err(ParseErrorReason::MissingCrlf);
         };
-        let mut i = 3;
-        let mut body_start = -1;
-        let mut response_data = String::new();
-        while i < bytes.len() {
-            let b = bytes[i];
-            if b == '\r' as u8 {
-                if i > 3 {
-                    response_data = if let Ok(s) = String::from_utf8(Vec::from(&bytes[3..i])) {
-                        s
-                    } else {
-                        return err;
-                    }
-                }
-                if let Some(&lf) = bytes.get(i + 1) {
-                    if lf == '\n' as u8 {
-                        crlf = true;
-                        i += 1;
-                    } else {
-                        return err;
-                    }
-                } else {
-                    return err;
-                }
-            } else if crlf {
-                body_start = i as i32;
-                break;
-            }
-            i += 1;
+        if bytes.get(cr + 1) != Some(&b'\n') {
+            return err(ParseErrorReason::MissingCrlf);
         }
-
-        let body_data = if body_start < 0 {
-            String::new()
-        } else {
-            if let Ok(s) = String::from_utf8(Vec::from(&bytes[body_start as usize..])) {
-                s
-            } else {
-                return err;
-            }
+        let Ok(response_data) = std::str::from_utf8(&bytes[3..cr]) else {
+            return err(ParseErrorReason::InvalidUtf8Meta);
         };
-        let res = {
-            if code >= 10 && code <= 19 {
-                Self::Input {
-                    kind: if code == 11 {
-                        InputKind::Sensitive
-                    } else {
-                        InputKind::Basic
-                    },
-                    prompt: response_data,
-                }
-            } else if code >= 20 && code <= 29 {
-                Self::Success { body: body_data }
-            } else if code >= 30 && code <= 39 {
-                Self::Redirection {
-                    kind: if code == 31 {
-                        RedirectionKind::Permanent
-                    } else {
-                        RedirectionKind::Temporary
-                    },
-                    to: response_data,
-                }
-            } else if code >= 40 && code <= 49 {
-                Self::TemporaryFailure {
-                    kind: if code == 41 {
-                        TemporaryFailureKind::ServerUnavailable
-                    } else if code == 42 {
-                        TemporaryFailureKind::CGIError
-                    } else if code == 43 {
-                        TemporaryFailureKind::ProxyError
-                    } else if code == 44 {
-                        TemporaryFailureKind::SlowDown
-                    } else {
-                        TemporaryFailureKind::Unspecified
-                    },
-                    msg: response_data,
-                }
-            } else if code >= 50 && code <= 59 {
-                Self::PermanentFailure {
-                    kind: if code == 51 {
-                        PermanentFailureKind::NotFound
-                    } else if code == 52 {
-                        PermanentFailureKind::Gone
-                    } else if code == 53 {
-                        PermanentFailureKind::ProxyRequestRefused
-                    } else if code == 59 {
-                        PermanentFailureKind::BadRequest
-                    } else {
-                        PermanentFailureKind::General
-                    },
-                    msg: response_data,
-                }
-            } else if code >= 60 && code <= 69 {
-                Self::ClientCertificate {
-                    kind: if code == 61 {
-                        CertificateErrorKind::CertificateNotAuthorized
-                    } else if code == 62 {
-                        CertificateErrorKind::CertificateNotValid
-                    } else {
-                        CertificateErrorKind::CertificateRequired
-                    },
-                    msg: response_data,
-                }
-            } else {
-                return err;
+        let response_data = response_data.to_string();
+        let body_bytes = &bytes[cr + 2..];
+        // The first digit gives the class, undefined codes get the default kind of their class
+        let res = match code / 10 {
+            1 => Self::Input {
+                code,
+                kind: match code {
+                    11 => InputKind::Sensitive,
+                    _ => InputKind::Basic,
+                },
+                prompt: response_data,
+            },
+            2 => {
+                let meta = SuccessMeta::parse(&response_data);
+                let body = if !meta.mime.starts_with("text/") {
+                    SuccessBody::Binary(body_bytes.to_vec())
+                } else if let Some(body) = meta.decode(body_bytes) {
+                    SuccessBody::Text(body)
+                } else {
+                    return err(ParseErrorReason::InvalidBody);
+                };
+                Self::Success { code, meta, body }
             }
+            3 => Self::Redirection {
+                code,
+                kind: match code {
+                    31 => RedirectionKind::Permanent,
+                    _ => RedirectionKind::Temporary,
+                },
+                to: response_data,
+            },
+            4 => Self::TemporaryFailure {
+                code,
+                kind: match code {
+                    41 => TemporaryFailureKind::ServerUnavailable,
+                    42 => TemporaryFailureKind::CGIError,
+                    43 => TemporaryFailureKind::ProxyError,
+                    44 => TemporaryFailureKind::SlowDown,
+                    _ => TemporaryFailureKind::Unspecified,
+                },
+                msg: response_data,
+            },
+            5 => Self::PermanentFailure {
+                code,
+                kind: match code {
+                    51 => PermanentFailureKind::NotFound,
+                    52 => PermanentFailureKind::Gone,
+                    53 => PermanentFailureKind::ProxyRequestRefused,
+                    59 => PermanentFailureKind::BadRequest,
+                    _ => PermanentFailureKind::General,
+                },
+                msg: response_data,
+            },
+            6 => Self::ClientCertificate {
+                code,
+                kind: match code {
+                    61 => CertificateErrorKind::CertificateNotAuthorized,
+                    62 => CertificateErrorKind::CertificateNotValid,
+                    _ => CertificateErrorKind::CertificateRequired,
+                },
+                msg: response_data,
+            },
+            _ => return err(ParseErrorReason::UnknownStatusClass),
         };
 
         Ok(res)
@@ -195,8 +383,153 @@ pub enum PermanentFailureKind {
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum CertificateErrorKind {
     CertificateRequired,      // 60
     CertificateNotAuthorized, // 61
     CertificateNotValid,      // 62
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_without_parameters() {
+        let meta = SuccessMeta::parse("text/plain");
+        assert_eq!(meta.mime, "text/plain");
+        assert_eq!(meta.charset, None);
+        assert_eq!(meta.lang, None);
+        assert_eq!(SuccessMeta::parse("").mime, "text/gemini");
+    }
+
+    #[test]
+    fn meta_with_parameters() {
+        let meta = SuccessMeta::parse("Text/Gemini; charset=\"UTF-8\" ;lang=en-GB; format=x");
+        assert_eq!(meta.mime, "text/gemini");
+        assert_eq!(meta.charset.as_deref(), Some("utf-8"));
+        assert_eq!(meta.lang.as_deref(), Some("en-GB"));
+    }
+
+    fn reason(bytes: &[u8]) -> ParseErrorReason {
+        GeminiResponse::from_bytes(bytes).unwrap_err().reason
+    }
+
+    #[test]
+    fn too_short() {
+        assert_eq!(reason(b""), ParseErrorReason::TooShort);
+        assert_eq!(reason(b"20"), ParseErrorReason::TooShort);
+    }
+
+    #[test]
+    fn bad_status() {
+        assert_eq!(reason(b"-1 x\r\n"), ParseErrorReason::BadStatus);
+    }
+
+    #[test]
+    fn missing_space() {
+        assert_eq!(reason(b"200 \r\n"), ParseErrorReason::MissingSpace);
+    }
+
+    #[test]
+    fn missing_crlf() {
+        assert_eq!(reason(b"20 text/gemini\n"), ParseErrorReason::MissingCrlf);
+        assert_eq!(reason(b"20 text/gemini\rx"), ParseErrorReason::MissingCrlf);
+    }
+
+    #[test]
+    fn invalid_utf8_meta() {
+        assert_eq!(
+            reason(b"20 text/\xff\r\n"),
+            ParseErrorReason::InvalidUtf8Meta
+        );
+    }
+
+    #[test]
+    fn unknown_status_class() {
+        assert_eq!(reason(b"00 x\r\n"), ParseErrorReason::UnknownStatusClass);
+        assert_eq!(reason(b"70 x\r\n"), ParseErrorReason::UnknownStatusClass);
+    }
+
+    #[test]
+    fn invalid_body() {
+        assert_eq!(
+            reason(b"20 text/plain\r\n\xff"),
+            ParseErrorReason::InvalidBody
+        );
+        // Binary bodies are kept as they are
+        assert!(GeminiResponse::from_bytes(b"20 image/png\r\n\xff").is_ok());
+    }
+
+    #[test]
+    fn status_is_two_digits_and_a_space() {
+        // Valid so far, the header just never ends
+        assert_eq!(reason(b"20 "), ParseErrorReason::MissingCrlf);
+        assert_eq!(reason(b"20X"), ParseErrorReason::MissingSpace);
+        assert_eq!(reason(b"2a text/gemini\r\n"), ParseErrorReason::BadStatus);
+        assert_eq!(reason(b"2 0 text/gemini\r\n"), ParseErrorReason::BadStatus);
+        assert_eq!(reason(b"+2 text/gemini\r\n"), ParseErrorReason::BadStatus);
+    }
+
+    #[test]
+    fn short_meta_and_body() {
+        // Input responses have no body, whatever follows the header is ignored
+        let GeminiResponse::Input { prompt, .. } =
+            GeminiResponse::from_bytes(b"10 ?\r\nX").unwrap()
+        else {
+            panic!("not an input response");
+        };
+        assert_eq!(prompt, "?");
+        let GeminiResponse::Success { meta, body, .. } =
+            GeminiResponse::from_bytes(b"20 \r\nbody").unwrap()
+        else {
+            panic!("not a success response");
+        };
+        assert_eq!(meta.mime, "text/gemini");
+        assert!(matches!(body, SuccessBody::Text(body) if body == "body"));
+        let GeminiResponse::Success { body, .. } =
+            GeminiResponse::from_bytes(b"20 text/plain\r\nX").unwrap()
+        else {
+            panic!("not a success response");
+        };
+        assert!(matches!(body, SuccessBody::Text(body) if body == "X"));
+    }
+
+    #[test]
+    fn boundary_status_codes() {
+        let parse = |raw: &[u8]| GeminiResponse::from_bytes(raw).unwrap();
+        let response = parse(b"10 Query\r\n");
+        assert!(matches!(
+            response,
+            GeminiResponse::Input {
+                kind: InputKind::Basic,
+                ..
+            }
+        ));
+        assert_eq!(response.code(), 10);
+        let response = parse(b"19 Query\r\n");
+        assert!(matches!(
+            response,
+            GeminiResponse::Input {
+                kind: InputKind::Basic,
+                ..
+            }
+        ));
+        assert_eq!(response.code(), 19);
+        let response = parse(b"20 text/gemini\r\n");
+        assert!(matches!(response, GeminiResponse::Success { .. }));
+        assert_eq!(response.code(), 20);
+        let response = parse(b"29 text/gemini\r\n");
+        assert!(matches!(response, GeminiResponse::Success { .. }));
+        assert_eq!(response.code(), 29);
+        let response = parse(b"69 Nope\r\n");
+        assert!(matches!(
+            response,
+            GeminiResponse::ClientCertificate {
+                kind: CertificateErrorKind::CertificateRequired,
+                ..
+            }
+        ));
+        assert_eq!(response.code(), 69);
+    }
+}
@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+use std::{str::FromStr, time::Duration};
+
+use remi::{verifier::VerificationMode, RequestOptions};
+
+use crate::{cache, theme::Theme, DEFAULT_URL};
+
+/// Search engine queried with what is typed in the URL bar when it isn't a URL
+pub const DEFAULT_SEARCH_URL: &str = "gemini://geminispace.info/search";
+/// Width of the reader mode column, in characters
+pub const DEFAULT_READER_WIDTH: usize = 70;
+pub const MIN_READER_WIDTH: usize = 20;
+pub const MAX_READER_WIDTH: usize = 300;
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 3.;
+pub const ZOOM_STEP: f32 = 0.1;
+/// Longest connect or read timeout that can be picked, in seconds
+pub const MAX_TIMEOUT: u64 = 300;
+
+const HOME_URL_STORE_KEY: &str = "home_url";
+const SEARCH_URL_STORE_KEY: &str = "search_url";
+const ZOOM_STORE_KEY: &str = "zoom";
+const DEFAULT_PORT_STORE_KEY: &str = "default_port";
+const CONNECT_TIMEOUT_STORE_KEY: &str = "connect_timeout";
+const READ_TIMEOUT_STORE_KEY: &str = "read_timeout";
+const CACHE_SIZE_STORE_KEY: &str = "cache_size";
+const COLLAPSE_BLANK_LINES_STORE_KEY: &str = "collapse_blank_lines";
+const LINKIFY_TEXT_STORE_KEY: &str = "linkify_text";
+const READER_MODE_STORE_KEY: &str = "reader_mode";
+const READER_WIDTH_STORE_KEY: &str = "reader_width";
+const SPARTAN_STORE_KEY: &str = "spartan";
+const RESTORE_SESSION_STORE_KEY: &str = "restore_session";
+const VERIFICATION_STORE_KEY: &str = "verification";
+const THEME_STORE_KEY: &str = "theme";
+
+/// User preferences, edited on the `about:settings` page and kept in the eframe storage.
+/// Each setting is stored under its own key, missing or invalid values keep their default.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub home_url: String,
+    /// URL the search queries are sent to, as its query
+    pub search_url: String,
+    /// Scale applied to the text sizes of the page
+    pub zoom: f32,
+    pub theme: Theme,
+    /// Theme loaded from the user's theme file, if there is a valid one. It isn't stored.
+    pub custom_theme: Option<Theme>,
+    pub request_options: RequestOptions,
+    /// Limit of the offline cache, in bytes
+    pub cache_size: u64,
+    /// Whether runs of blank lines are shown as a single one
+    pub collapse_blank_lines: bool,
+    /// Whether URLs in text lines are shown as links
+    pub linkify_text: bool,
+    /// Whether the tabs of the last session are reopened at startup
+    pub restore_session: bool,
+    /// Whether the page is shown in a centered column of `reader_width` characters
+    pub reader_mode: bool,
+    pub reader_width: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            home_url: DEFAULT_URL.to_string(),
+            search_url: DEFAULT_SEARCH_URL.to_string(),
+            zoom: 1.,
+            theme: Theme::default(),
+            custom_theme: None,
+            request_options: RequestOptions::default(),
+            cache_size: cache::DEFAULT_MAX_SIZE,
+            collapse_blank_lines: false,
+            linkify_text: false,
+            restore_session: true,
+            reader_mode: false,
+            reader_width: DEFAULT_READER_WIDTH,
+        }
+    }
+}
+
+impl Settings {
+    /// Defaults, with the custom theme selected when there is one
+    pub fn new(custom_theme: Option<Theme>) -> Self {
+        Self {
+            theme: custom_theme.unwrap_or_default(),
+            custom_theme,
+            ..Self::default()
+        }
+    }
+
+    pub fn load(storage: &dyn eframe::Storage, custom_theme: Option<Theme>) -> Self {
+        let mut res = Self::new(custom_theme);
+        let options = &mut res.request_options;
+        if let Some(home_url) = storage.get_string(HOME_URL_STORE_KEY) {
+            res.home_url = home_url;
+        }
+        if let Some(search_url) = storage.get_string(SEARCH_URL_STORE_KEY) {
+            res.search_url = search_url;
+        }
+        if let Some(zoom) = get::<f32>(storage, ZOOM_STORE_KEY) {
+            res.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        }
+        if let Some(theme) = storage.get_string(THEME_STORE_KEY) {
+            // The custom theme stays selected unless a built-in one was picked
+            res.theme = Theme::from_name(&theme).unwrap_or(res.theme);
+        }
+        // Port 0 can't be connected to
+        if let Some(port) = get::<u16>(storage, DEFAULT_PORT_STORE_KEY).filter(|&port| port != 0) {
+            options.default_port = port;
+        }
+        if let Some(secs) = get::<u64>(storage, CONNECT_TIMEOUT_STORE_KEY) {
+            options.connect_timeout = Duration::from_secs(secs.clamp(1, MAX_TIMEOUT));
+        }
+        if let Some(secs) = get::<u64>(storage, READ_TIMEOUT_STORE_KEY) {
+            options.read_timeout = Duration::from_secs(secs.clamp(1, MAX_TIMEOUT));
+        }
+        if let Some(verification) = storage.get_string(VERIFICATION_STORE_KEY) {
+            options.verification =
+                VerificationMode::from_name(&verification).unwrap_or(options.verification);
+        }
+        if let Some(spartan) = get(storage, SPARTAN_STORE_KEY) {
+            options.spartan = spartan;
+        }
+        if let Some(cache_size) = get(storage, CACHE_SIZE_STORE_KEY) {
+            res.cache_size = cache_size;
+        }
+        if let Some(collapse) = get(storage, COLLAPSE_BLANK_LINES_STORE_KEY) {
+            res.collapse_blank_lines = collapse;
+        }
+        if let Some(linkify) = get(storage, LINKIFY_TEXT_STORE_KEY) {
+            res.linkify_text = linkify;
+        }
+        if let Some(restore) = get(storage, RESTORE_SESSION_STORE_KEY) {
+            res.restore_session = restore;
+        }
+        if let Some(reader_mode) = get(storage, READER_MODE_STORE_KEY) {
+            res.reader_mode = reader_mode;
+        }
+        if let Some(reader_width) = get::<usize>(storage, READER_WIDTH_STORE_KEY) {
+            res.reader_width = reader_width.clamp(MIN_READER_WIDTH, MAX_READER_WIDTH);
+        }
+        res
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let options = &self.request_options;
+        storage.set_string(HOME_URL_STORE_KEY, self.home_url.clone());
+        storage.set_string(SEARCH_URL_STORE_KEY, self.search_url.clone());
+        storage.set_string(ZOOM_STORE_KEY, self.zoom.to_string());
+        let theme_name = if Some(self.theme) == self.custom_theme {
+            "custom"
+        } else {
+            self.theme.name()
+        };
+        storage.set_string(THEME_STORE_KEY, theme_name.to_string());
+        storage.set_string(DEFAULT_PORT_STORE_KEY, options.default_port.to_string());
+        storage.set_string(
+            CONNECT_TIMEOUT_STORE_KEY,
+            options.connect_timeout.as_secs().to_string(),
+        );
+        storage.set_string(
+            READ_TIMEOUT_STORE_KEY,
+            options.read_timeout.as_secs().to_string(),
+        );
+        storage.set_string(
+            VERIFICATION_STORE_KEY,
+            options.verification.name().to_string(),
+        );
+        storage.set_string(SPARTAN_STORE_KEY, options.spartan.to_string());
+        storage.set_string(CACHE_SIZE_STORE_KEY, self.cache_size.to_string());
+        storage.set_string(
+            COLLAPSE_BLANK_LINES_STORE_KEY,
+            self.collapse_blank_lines.to_string(),
+        );
+        storage.set_string(LINKIFY_TEXT_STORE_KEY, self.linkify_text.to_string());
+        storage.set_string(RESTORE_SESSION_STORE_KEY, self.restore_session.to_string());
+        storage.set_string(READER_MODE_STORE_KEY, self.reader_mode.to_string());
+        storage.set_string(READER_WIDTH_STORE_KEY, self.reader_width.to_string());
+    }
+}
+
+fn get<T: FromStr>(storage: &dyn eframe::Storage, key: &str) -> Option<T> {
+    storage.get_string(key)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct Storage(HashMap<String, String>);
+
+    impl eframe::Storage for Storage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn load_port(port: &str) -> u16 {
+        let storage = Storage(HashMap::from([(
+            DEFAULT_PORT_STORE_KEY.to_string(),
+            port.to_string(),
+        )]));
+        Settings::load(&storage, None).request_options.default_port
+    }
+
+    #[test]
+    fn stored_default_port_is_validated() {
+        assert_eq!(load_port("1966"), 1966);
+        assert_eq!(load_port("1"), 1);
+        assert_eq!(load_port("65535"), u16::MAX);
+        assert_eq!(load_port("0"), 1965);
+        assert_eq!(load_port("65536"), 1965);
+        assert_eq!(load_port("-1"), 1965);
+    }
+}
@@ -0,0 +1,165 @@
+#![allow(dead_code)]
+use remi::gemtext::{Gemtext, GemtextEntry};
+use remi::url::GeminiUrl;
+
+/// A dated link of a feed page, `=> url YYYY-MM-DD title`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    pub url: String,
+    pub date: String,
+    pub title: String,
+}
+
+/// A page followed for new dated links, as described by the Gemini subscription companion spec
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub url: String,
+    pub title: String,
+    /// Entries found the last time the page was loaded, they aren't stored across sessions
+    pub entries: Vec<FeedEntry>,
+    /// URLs of the entries that were already seen
+    pub seen: Vec<String>,
+}
+
+impl Subscription {
+    pub fn new(url: &str, title: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            title: title.to_string(),
+            entries: vec![],
+            seen: vec![],
+        }
+    }
+
+    /// Text shown for the subscription, the URL when it has no title
+    pub fn name(&self) -> &str {
+        if self.title.trim().is_empty() {
+            &self.url
+        } else {
+            &self.title
+        }
+    }
+
+    pub fn unread(&self) -> impl Iterator<Item = &FeedEntry> {
+        self.entries.iter().filter(|e| !self.seen.contains(&e.url))
+    }
+
+    /// Replaces the entries with the ones of `gemtext`, the page at `self.url`.
+    /// Entries that left the page are forgotten so that `seen` doesn't grow forever.
+    pub fn update(&mut self, gemtext: &Gemtext) {
+        self.entries = entries(&self.url, gemtext);
+        let entries = &self.entries;
+        self.seen
+            .retain(|url| entries.iter().any(|e| &e.url == url));
+    }
+
+    pub fn mark_all_seen(&mut self) {
+        self.seen = self.entries.iter().map(|e| e.url.clone()).collect();
+    }
+
+    /// Marks the entry linking to `url` as seen, returns whether there was an unread one
+    pub fn mark_seen(&mut self, url: &str) -> bool {
+        let unread = self.unread().any(|e| e.url == url);
+        if unread {
+            self.seen.push(url.to_string());
+        }
+        unread
+    }
+
+    /// Parses a `url[ title]` line
+    fn from_line(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        Some(match line.split_once(' ') {
+            Some((url, title)) => Self::new(url, title.trim()),
+            None => Self::new(line, ""),
+        })
+    }
+}
+
+/// Dated links of the feed page `gemtext` found at `base`, with their URLs made absolute
+pub fn entries(base: &str, gemtext: &Gemtext) -> Vec<FeedEntry> {
+    let base = GeminiUrl::parse(base).ok();
+    gemtext
+        .links()
+        .filter_map(|(url, label)| {
+            let label = label.trim();
+            let date = label.get(..10).filter(|date| is_date(date))?;
+            // Feeds often separate the date from the title with a dash
+            let title = label[10..].trim_start_matches(|c: char| c.is_whitespace() || c == '-');
+            let url = base
+                .as_ref()
+                .and_then(|base| base.join(url).ok())
+                .map_or_else(|| url.to_string(), |url| url.to_string());
+            Some(FeedEntry {
+                url,
+                date: date.to_string(),
+                title: title.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether `s` is a `YYYY-MM-DD` date
+fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// Parses subscriptions written by `to_lines`
+pub fn from_lines(lines: &str) -> Vec<Subscription> {
+    let mut res: Vec<Subscription> = vec![];
+    for line in lines.lines() {
+        // Seen entries are indented under their subscription
+        if let Some(url) = line.strip_prefix('\t') {
+            if let Some(subscription) = res.last_mut() {
+                subscription.seen.push(url.trim().to_string());
+            }
+        } else if let Some(subscription) = Subscription::from_line(line) {
+            res.push(subscription);
+        }
+    }
+    res
+}
+
+/// One `url[ title]` line per subscription, followed by a tab-indented line per seen entry
+pub fn to_lines(subscriptions: &[Subscription]) -> String {
+    let mut res = vec![];
+    for subscription in subscriptions {
+        let title = subscription.title.replace(['\r', '\n'], " ");
+        res.push(
+            format!("{} {}", subscription.url, title.trim())
+                .trim_end()
+                .to_string(),
+        );
+        res.extend(subscription.seen.iter().map(|url| format!("\t{url}")));
+    }
+    res.join("\n")
+}
+
+/// Gemtext document listing the unread entries of every subscription, under a `##` heading per feed
+pub fn to_gemtext(subscriptions: &[Subscription]) -> Gemtext {
+    let mut data = vec![GemtextEntry::MajorHeading("Subscriptions".to_string())];
+    for subscription in subscriptions {
+        let unread: Vec<_> = subscription.unread().collect();
+        data.push(GemtextEntry::MediumHeading(format!(
+            "{} ({} new)",
+            subscription.name(),
+            unread.len()
+        )));
+        data.push(GemtextEntry::Link {
+            url: subscription.url.clone(),
+            label: "Open feed".to_string(),
+        });
+        data.extend(unread.into_iter().map(|entry| GemtextEntry::Link {
+            url: entry.url.clone(),
+            label: format!("{} {}", entry.date, entry.title),
+        }));
+    }
+    Gemtext { data }
+}
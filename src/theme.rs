@@ -0,0 +1,205 @@
+#![allow(dead_code)]
+use std::error::Error;
+
+use eframe::egui::{Color32, Visuals};
+
+/// Name of the user theme file in the storage directory
+pub const THEME_FILE_NAME: &str = "theme.toml";
+
+/// Colors used to draw the interface and the pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub dark: bool,
+    pub bg: Color32,
+    pub hovered_bg: Color32,
+    pub text: Color32,
+    pub bright_text: Color32,
+    pub heading: Color32,
+    pub list: Color32,
+    pub link: Color32,
+    pub external_link: Color32,
+    /// Links from an encrypted page to plain http, see `is_insecure_link`
+    pub insecure_link: Color32,
+    /// Bar along the side of quotes
+    pub quote_bar: Color32,
+    pub preformatted_bg: Color32,
+    pub preformatted_text: Color32,
+    pub alt_text: Color32,
+    pub error: Color32,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        dark: true,
+        bg: Color32::from_rgb(40, 44, 52),
+        hovered_bg: Color32::from_rgb(48, 54, 64),
+        text: Color32::from_rgb(171, 178, 191),
+        bright_text: Color32::from_rgb(201, 208, 221),
+        heading: Color32::from_rgb(190, 96, 105),
+        list: Color32::from_rgb(201, 208, 221),
+        link: Color32::from_rgb(86, 182, 194),
+        external_link: Color32::from_rgb(198, 120, 221),
+        insecure_link: Color32::from_rgb(229, 192, 123),
+        quote_bar: Color32::from_rgb(152, 195, 121),
+        preformatted_bg: Color32::from_rgb(25, 27, 31),
+        preformatted_text: Color32::from_rgb(156, 163, 176),
+        alt_text: Color32::from_rgb(92, 99, 112),
+        error: Color32::from_rgb(190, 96, 105),
+    };
+
+    pub const LIGHT: Theme = Theme {
+        dark: false,
+        bg: Color32::from_rgb(250, 250, 250),
+        hovered_bg: Color32::from_rgb(234, 234, 235),
+        text: Color32::from_rgb(56, 58, 66),
+        bright_text: Color32::from_rgb(18, 20, 24),
+        heading: Color32::from_rgb(202, 18, 67),
+        list: Color32::from_rgb(18, 20, 24),
+        link: Color32::from_rgb(1, 132, 188),
+        external_link: Color32::from_rgb(166, 38, 164),
+        insecure_link: Color32::from_rgb(193, 132, 1),
+        quote_bar: Color32::from_rgb(80, 161, 79),
+        preformatted_bg: Color32::from_rgb(234, 234, 235),
+        preformatted_text: Color32::from_rgb(64, 66, 74),
+        alt_text: Color32::from_rgb(160, 161, 167),
+        error: Color32::from_rgb(202, 18, 67),
+    };
+
+    /// Name stored in the settings, see `from_name`
+    pub fn name(&self) -> &'static str {
+        if self.dark {
+            "dark"
+        } else {
+            "light"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::DARK),
+            "light" => Some(Self::LIGHT),
+            _ => None,
+        }
+    }
+
+    /// Parses a theme file made of `key = "#rrggbb"` lines, plus an optional `dark = true|false`.
+    /// This is the flat subset of TOML, `#` comments and `[table]` headers are ignored.
+    /// Missing colors are taken from the dark or light built-in theme, depending on `dark`.
+    pub fn from_toml(s: &str) -> Result<Self, ThemeParseError> {
+        let mut entries = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ThemeParseError::new(i + 1, "expected 'key = value'"));
+            };
+            let value = value.trim();
+            // Comments may follow the value, but colors contain '#' themselves
+            let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''));
+            let value = if let Some(quote) = quote {
+                let Some((value, rest)) = value[1..].split_once(quote) else {
+                    return Err(ThemeParseError::new(i + 1, "unterminated string"));
+                };
+                let rest = rest.trim();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(ThemeParseError::new(
+                        i + 1,
+                        "unexpected text after the value",
+                    ));
+                }
+                value
+            } else {
+                value.split('#').next().unwrap_or_default().trim()
+            };
+            entries.push((i + 1, key.trim(), value));
+        }
+
+        let mut res = Self::DARK;
+        for &(line_number, key, value) in &entries {
+            if key == "dark" {
+                res = match value {
+                    "true" => Self::DARK,
+                    "false" => Self::LIGHT,
+                    _ => return Err(ThemeParseError::new(line_number, "expected true or false")),
+                };
+            }
+        }
+        for (line_number, key, value) in entries {
+            let color = match key {
+                "dark" => continue,
+                "bg" => &mut res.bg,
+                "hovered_bg" => &mut res.hovered_bg,
+                "text" => &mut res.text,
+                "bright_text" => &mut res.bright_text,
+                "heading" => &mut res.heading,
+                "list" => &mut res.list,
+                "link" => &mut res.link,
+                "external_link" => &mut res.external_link,
+                "insecure_link" => &mut res.insecure_link,
+                "quote_bar" => &mut res.quote_bar,
+                "preformatted_bg" => &mut res.preformatted_bg,
+                "preformatted_text" => &mut res.preformatted_text,
+                "alt_text" => &mut res.alt_text,
+                "error" => &mut res.error,
+                _ => return Err(ThemeParseError::new(line_number, "unknown key")),
+            };
+            *color = parse_color(value)
+                .ok_or_else(|| ThemeParseError::new(line_number, "expected a '#rrggbb' color"))?;
+        }
+        Ok(res)
+    }
+
+    /// egui visuals matching the theme
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = if self.dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        };
+        visuals.panel_fill = self.bg;
+        visuals.window_fill = self.bg;
+        visuals.hyperlink_color = self.link;
+        visuals
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DARK
+    }
+}
+
+fn parse_color(s: &str) -> Option<Color32> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[derive(Debug)]
+pub struct ThemeParseError {
+    /// 1-based line number in the theme file
+    pub line_number: usize,
+    pub msg: String,
+}
+
+impl ThemeParseError {
+    pub fn new(line_number: usize, msg: &str) -> Self {
+        Self {
+            line_number,
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ThemeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.msg)
+    }
+}
+
+impl Error for ThemeParseError {}
@@ -0,0 +1,515 @@
+#![allow(dead_code)]
+use std::error::Error;
+
+/// An absolute URL of the form `scheme://host[:port][/path][?query][#fragment]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeminiUrl {
+    pub scheme: String,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Segments of the path after the leading `/`, a trailing `/` is kept as an empty last segment.
+    /// An empty vector means the URL has no path at all.
+    pub path: Vec<String>,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct UrlParseError {
+    pub url: String,
+    pub msg: String,
+}
+
+impl UrlParseError {
+    pub fn new(url: &str, msg: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for UrlParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a valid URL: {}", self.url, self.msg)
+    }
+}
+
+impl Error for UrlParseError {}
+
+impl std::fmt::Display for GeminiUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.authority())?;
+        if !self.path.is_empty() {
+            write!(f, "/{}", self.path.join("/"))?;
+        }
+        if let Some(query) = &self.query {
+            write!(f, "?{query}")?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{fragment}")?;
+        }
+        Ok(())
+    }
+}
+
+impl GeminiUrl {
+    pub fn parse(url: &str) -> Result<Self, UrlParseError> {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return Err(UrlParseError::new(url, "missing scheme"));
+        };
+        if !is_scheme(scheme) {
+            return Err(UrlParseError::new(url, "invalid scheme"));
+        }
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+            None => (rest, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query.to_string())),
+            None => (rest, None),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], split_path(&rest[i..])),
+            None => (rest, vec![]),
+        };
+        let (host, port) = split_host_port(authority);
+        if host.is_empty() {
+            return Err(UrlParseError::new(url, "missing host"));
+        }
+        let port = match port {
+            Some(port) => Some(
+                port.parse()
+                    .map_err(|_| UrlParseError::new(url, "invalid port"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            scheme: scheme.to_ascii_lowercase(),
+            host: host.to_ascii_lowercase(),
+            port,
+            path,
+            query,
+            fragment,
+        })
+    }
+
+    /// Resolves `reference` against `self` following RFC 3986 section 5.2
+    ///
+    /// ```
+    /// use remi::url::GeminiUrl;
+    ///
+    /// let base = GeminiUrl::parse("gemini://example.org/docs/index.gmi").unwrap();
+    /// let url = base.join("../about.gmi").unwrap();
+    /// assert_eq!(url.to_string(), "gemini://example.org/about.gmi");
+    /// ```
+    pub fn join(&self, reference: &str) -> Result<Self, UrlParseError> {
+        if let Some((scheme, _)) = reference.split_once(':') {
+            if is_scheme(scheme) {
+                return Self::parse(reference);
+            }
+        }
+        if reference.starts_with("//") {
+            return Self::parse(&format!("{}:{reference}", self.scheme));
+        }
+
+        let (reference, fragment) = match reference.split_once('#') {
+            Some((reference, fragment)) => (reference, Some(fragment.to_string())),
+            None => (reference, None),
+        };
+        let (path, query) = match reference.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (reference, None),
+        };
+        let mut res = self.clone();
+        res.fragment = fragment;
+        if path.is_empty() {
+            // `?query` replaces the whole query of the base, never appends to it
+            if query.is_some() {
+                res.query = query;
+            }
+            return Ok(res);
+        }
+        res.query = query;
+        res.path = if path.starts_with('/') {
+            remove_dot_segments(split_path(path))
+        } else {
+            // Merge with everything up to the last `/` of the base path
+            let mut merged = res.path;
+            merged.pop();
+            merged.extend(path.split('/').map(|s| s.to_string()));
+            remove_dot_segments(merged)
+        };
+        Ok(res)
+    }
+
+    /// Same URL with its query replaced by `query`, which must already be percent-encoded.
+    /// The fragment is dropped as it pointed into the previous response.
+    pub fn with_query(&self, query: &str) -> Self {
+        Self {
+            query: Some(query.to_string()),
+            fragment: None,
+            ..self.clone()
+        }
+    }
+
+    /// URL of the directory above `self`, without its query and fragment.
+    /// `None` at the root of the host, which has no parent.
+    ///
+    /// ```
+    /// use remi::url::GeminiUrl;
+    ///
+    /// let parent = |url| GeminiUrl::parse(url).unwrap().parent().map(|url| url.to_string());
+    /// assert_eq!(parent("gemini://h/a/b/c.gmi?q").as_deref(), Some("gemini://h/a/b/"));
+    /// assert_eq!(parent("gemini://h/a/b/").as_deref(), Some("gemini://h/a/"));
+    /// assert_eq!(parent("gemini://h/a").as_deref(), Some("gemini://h/"));
+    /// assert_eq!(parent("gemini://h/"), None);
+    /// assert_eq!(parent("gemini://h"), None);
+    /// ```
+    pub fn parent(&self) -> Option<Self> {
+        let mut path = self.path.clone();
+        // A trailing `/` names the directory itself, its parent is one more level up
+        if path.last().is_some_and(|segment| segment.is_empty()) {
+            path.pop();
+        }
+        path.pop()?;
+        path.push(String::new());
+        Some(Self {
+            path,
+            query: None,
+            fragment: None,
+            ..self.clone()
+        })
+    }
+
+    /// `host[:port]`, as used to open the connection
+    pub fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match self.port {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        }
+    }
+}
+
+/// Lowercase scheme of `url`, if it has one
+pub fn scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.trim().split_once(':')?;
+    is_scheme(scheme).then(|| scheme.to_ascii_lowercase())
+}
+
+fn is_scheme(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Splits an absolute path into its segments, `path` must start with `/`
+fn split_path(path: &str) -> Vec<String> {
+    path[1..].split('/').map(|s| s.to_string()).collect()
+}
+
+fn remove_dot_segments(segments: Vec<String>) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    let last = segments.len().saturating_sub(1);
+    for (i, segment) in segments.into_iter().enumerate() {
+        match segment.as_str() {
+            "." => (),
+            ".." => {
+                res.pop();
+            }
+            _ => {
+                res.push(segment);
+                continue;
+            }
+        }
+        // A dot segment at the end still refers to a directory
+        if i == last {
+            res.push(String::new());
+        }
+    }
+    res
+}
+
+/// Splits `authority` into its host and optional port.
+/// Bracketed IPv6 literals like `[::1]:1966` are returned without their brackets.
+pub fn split_host_port(authority: &str) -> (&str, Option<&str>) {
+    if let Some(rest) = authority.strip_prefix('[') {
+        if let Some((host, rest)) = rest.split_once(']') {
+            return (host, rest.strip_prefix(':'));
+        }
+    }
+    match authority.split_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    }
+}
+
+/// Percent-encodes every byte of `input` outside the unreserved set of RFC 3986
+pub fn percent_encode(input: &str) -> String {
+    let mut res = String::with_capacity(input.len());
+    for b in input.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{b:02X}"));
+        }
+    }
+    res
+}
+
+/// `url` as it's shown to the user. Only escapes that would be written again when the URL is
+/// submitted are decoded, like those of spaces and non-ASCII text. Escapes of reserved characters,
+/// `%`, control characters and trailing whitespace are kept, decoding them would change the URL.
+pub fn decode_for_display(url: &str) -> String {
+    let mut res = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(i) = rest.find('%') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i..];
+        // Consecutive escapes are decoded together, they may make up a multi-byte character
+        let mut bytes = vec![];
+        while let Some(b) = escaped_byte(&rest[3 * bytes.len()..]) {
+            bytes.push(b);
+        }
+        if bytes.is_empty() {
+            res.push('%');
+            rest = &rest[1..];
+            continue;
+        }
+        let (escapes, after) = rest.split_at(3 * bytes.len());
+        let mut offset = 0;
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                let end = offset + c.len_utf8();
+                let trailing = end == bytes.len() && after.is_empty();
+                if is_shown_decoded(c) && !(trailing && c.is_whitespace()) {
+                    res.push(c);
+                } else {
+                    res.push_str(&escapes[3 * offset..3 * end]);
+                }
+                offset = end;
+            }
+            let end = offset + chunk.invalid().len();
+            res.push_str(&escapes[3 * offset..3 * end]);
+            offset = end;
+        }
+        rest = after;
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Byte of the `%XX` escape starting `s`
+fn escaped_byte(s: &str) -> Option<u8> {
+    let hex = s.strip_prefix('%')?.get(..2)?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Whether the escape of `c` can be shown decoded, the unreserved characters and the characters
+/// that are always percent-encoded before a request is sent
+fn is_shown_decoded(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '-' | '.' | '_' | '~' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}'
+        )
+        || (!c.is_ascii() && !c.is_control())
+        || c == ' '
+}
+
+/// Decodes every `%XX` escape in `input`, malformed escapes are kept as they are
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(b) = escaped {
+            res.push(b);
+            i += 3;
+        } else {
+            res.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(base: &str, reference: &str) -> String {
+        GeminiUrl::parse(base)
+            .unwrap()
+            .join(reference)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn percent_encode_reserved_and_unicode() {
+        assert_eq!(percent_encode("a b"), "a%20b");
+        assert_eq!(percent_encode("a&b=c"), "a%26b%3Dc");
+        assert_eq!(percent_encode("#heading"), "%23heading");
+        assert_eq!(percent_encode("café"), "caf%C3%A9");
+        assert_eq!(percent_encode("日本"), "%E6%97%A5%E6%9C%AC");
+        assert_eq!(percent_encode("a-b.c_d~"), "a-b.c_d~");
+        assert_eq!(percent_decode("caf%C3%A9%20%26%23"), "café &#");
+    }
+
+    #[test]
+    fn decode_for_display_keeps_meaningful_escapes() {
+        assert_eq!(
+            decode_for_display("gemini://h/caf%C3%A9%20au%20lait"),
+            "gemini://h/café au lait"
+        );
+        assert_eq!(decode_for_display("gemini://h/%41%7e"), "gemini://h/A~");
+        for url in [
+            "gemini://h/a%2Fb",
+            "gemini://h/a%3Fb",
+            "gemini://h/a%23b",
+            "gemini://h/100%25",
+            "gemini://h/a%26b%3Dc",
+            "gemini://h/a%0Ab",
+            "gemini://h/a%C3",
+            "gemini://h/a%zz",
+            "gemini://h/a%",
+            "gemini://h/search?a%20",
+        ] {
+            assert_eq!(decode_for_display(url), url);
+        }
+        assert_eq!(
+            decode_for_display("gemini://h/%C3%A9%C3%2F"),
+            "gemini://h/é%C3%2F"
+        );
+    }
+
+    #[test]
+    fn join_against_file() {
+        assert_eq!(join("gemini://h/a/b.gmi", "c"), "gemini://h/a/c");
+        assert_eq!(join("gemini://h/a/b.gmi", "c.gmi"), "gemini://h/a/c.gmi");
+        assert_eq!(join("gemini://h/a/b", "c/d.gmi"), "gemini://h/a/c/d.gmi");
+        assert_eq!(join("gemini://h/a/b.gmi", "./"), "gemini://h/a/");
+    }
+
+    #[test]
+    fn join_against_directory() {
+        assert_eq!(join("gemini://h/a/", "c"), "gemini://h/a/c");
+        assert_eq!(join("gemini://h/a/b/", "c.gmi"), "gemini://h/a/b/c.gmi");
+        assert_eq!(join("gemini://h/", "c"), "gemini://h/c");
+        assert_eq!(join("gemini://h", "c"), "gemini://h/c");
+    }
+
+    #[test]
+    fn split_host_and_port() {
+        assert_eq!(
+            split_host_port("example.org:1966"),
+            ("example.org", Some("1966"))
+        );
+        assert_eq!(split_host_port("example.org"), ("example.org", None));
+        let url = GeminiUrl::parse("gemini://example.org:1966/a").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("example.org", Some(1966)));
+    }
+
+    #[test]
+    fn split_ipv6_literals() {
+        assert_eq!(split_host_port("[::1]"), ("::1", None));
+        assert_eq!(split_host_port("[::1]:1966"), ("::1", Some("1966")));
+        assert_eq!(
+            split_host_port("[2001:db8::1]:1965"),
+            ("2001:db8::1", Some("1965"))
+        );
+        assert_eq!(split_host_port("127.0.0.1"), ("127.0.0.1", None));
+        assert_eq!(
+            split_host_port("127.0.0.1:1966"),
+            ("127.0.0.1", Some("1966"))
+        );
+        assert_eq!(split_host_port("localhost"), ("localhost", None));
+    }
+
+    #[test]
+    fn join_dot_segments() {
+        assert_eq!(
+            join("gemini://h/a/b/c.gmi", "../d.gmi"),
+            "gemini://h/a/d.gmi"
+        );
+        assert_eq!(
+            join("gemini://h/a/b/c.gmi", "../../d.gmi"),
+            "gemini://h/d.gmi"
+        );
+        assert_eq!(join("gemini://h/a/b/c.gmi", "../"), "gemini://h/a/");
+        assert_eq!(
+            join("gemini://h/a/b/c.gmi", "./d.gmi"),
+            "gemini://h/a/b/d.gmi"
+        );
+        assert_eq!(join("gemini://h/a/", "../../.."), "gemini://h/");
+    }
+
+    #[test]
+    fn join_absolute_path_and_query() {
+        assert_eq!(join("gemini://h/a/b.gmi", "/c/d.gmi"), "gemini://h/c/d.gmi");
+        assert_eq!(join("gemini://h/a/b.gmi?x", "/"), "gemini://h/");
+        assert_eq!(join("gemini://h/a/b.gmi", "?q"), "gemini://h/a/b.gmi?q");
+        assert_eq!(
+            join("gemini://h/a/b.gmi", "gemini://other.org/c"),
+            "gemini://other.org/c"
+        );
+        assert_eq!(
+            join("gemini://h/a/b.gmi", "//other.org/c"),
+            "gemini://other.org/c"
+        );
+    }
+
+    #[test]
+    fn join_keeps_explicit_port() {
+        assert_eq!(
+            join("gemini://h:1966/a/b.gmi", "c.gmi"),
+            "gemini://h:1966/a/c.gmi"
+        );
+        assert_eq!(join("gemini://h:1966/a/b.gmi", "/c"), "gemini://h:1966/c");
+        assert_eq!(join("gemini://h/a", "//h:1966/c"), "gemini://h:1966/c");
+        assert_eq!(
+            GeminiUrl::parse("gemini://h:1966").unwrap().port,
+            Some(1966)
+        );
+        assert!(GeminiUrl::parse("gemini://h:99999/").is_err());
+    }
+
+    #[test]
+    fn join_query_and_fragment_only() {
+        assert_eq!(
+            join("gemini://h/search?old", "?new"),
+            "gemini://h/search?new"
+        );
+        assert_eq!(join("gemini://h/a/#top", "?q"), "gemini://h/a/?q");
+        assert_eq!(
+            join("gemini://h/a.gmi?q", "#part"),
+            "gemini://h/a.gmi?q#part"
+        );
+        assert_eq!(join("gemini://h/a.gmi#old", "#new"), "gemini://h/a.gmi#new");
+        assert_eq!(join("gemini://h/a.gmi?q#old", ""), "gemini://h/a.gmi?q");
+    }
+
+    #[test]
+    fn with_query_replaces_query() {
+        let url = GeminiUrl::parse("gemini://h/search?old#results").unwrap();
+        assert_eq!(
+            url.with_query("new%20input").to_string(),
+            "gemini://h/search?new%20input"
+        );
+        let url = GeminiUrl::parse("gemini://h/search").unwrap();
+        assert_eq!(url.with_query("new").to_string(), "gemini://h/search?new");
+    }
+}
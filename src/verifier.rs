@@ -1,56 +1,211 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
 use rustls::{
     client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
-    pki_types::{CertificateDer, ServerName, UnixTime},
+    crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime},
+    RootCertStore,
 };
 
+/// How server certificates are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerificationMode {
+    /// Pin the certificate of each host on first use, see `GeminiCertVerifier`
+    #[default]
+    Tofu,
+    /// Accept any certificate without pinning it
+    TrustAll,
+    /// Only accept certificates signed by the system certificate authorities
+    Strict,
+}
+
+impl VerificationMode {
+    pub const ALL: [Self; 3] = [Self::Tofu, Self::TrustAll, Self::Strict];
+
+    /// Name stored in the settings, see `from_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tofu => "tofu",
+            Self::TrustAll => "trust_all",
+            Self::Strict => "strict",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Tofu => "TOFU (recommended)",
+            Self::TrustAll => "Trust all (insecure)",
+            Self::Strict => "Strict (system roots)",
+        }
+    }
+}
+
+/// Usual locations of the CA bundle on Unix systems, `SSL_CERT_FILE` takes precedence
+const CA_BUNDLE_PATHS: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+/// Certificate authorities of the system, read from the first CA bundle found.
+/// Empty if there isn't one, in which case every certificate is rejected.
+pub fn system_roots() -> Arc<RootCertStore> {
+    static ROOTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
+    ROOTS
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            let env_path = std::env::var("SSL_CERT_FILE").ok();
+            let bundle = env_path
+                .iter()
+                .map(String::as_str)
+                .chain(CA_BUNDLE_PATHS)
+                .find_map(|path| std::fs::read(path).ok());
+            if let Some(bundle) = bundle {
+                roots.add_parsable_certificates(
+                    CertificateDer::pem_slice_iter(&bundle).filter_map(Result::ok),
+                );
+            }
+            Arc::new(roots)
+        })
+        .clone()
+}
+
+/// Certificate fingerprints trusted on first use, keyed by host
+#[derive(Debug, Default)]
+pub struct KnownHosts {
+    pub fingerprints: HashMap<String, String>,
+    /// Set by the verifier when a host presents a different certificate than the pinned one
+    pub mismatch: Option<CertificateMismatch>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateMismatch {
+    pub host: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl KnownHosts {
+    /// Parses lines of `host fingerprint`
+    pub fn from_lines(s: &str) -> Self {
+        Self {
+            fingerprints: s
+                .lines()
+                .filter_map(|l| l.split_once(' '))
+                .map(|(host, fingerprint)| (host.to_string(), fingerprint.to_string()))
+                .collect(),
+            mismatch: None,
+        }
+    }
+
+    pub fn to_lines(&self) -> String {
+        let mut res = String::new();
+        for (host, fingerprint) in &self.fingerprints {
+            res.push_str(host);
+            res.push(' ');
+            res.push_str(fingerprint);
+            res.push('\n');
+        }
+        res.pop();
+        res
+    }
+}
+
+/// Hex encoded SHA-256 digest of a DER certificate
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert.as_ref())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Pins the certificate of each host on first use instead of checking it against CAs,
+/// as most Gemini capsules use self-signed certificates
 #[derive(Debug)]
-pub struct GeminiCertVerifier {}
+pub struct GeminiCertVerifier {
+    pub known_hosts: Arc<Mutex<KnownHosts>>,
+    /// Accept every certificate and leave `known_hosts` untouched
+    pub trust_all: bool,
+}
+
+impl GeminiCertVerifier {
+    fn provider() -> &'static CryptoProvider {
+        CryptoProvider::get_default().expect("a default crypto provider is installed in main")
+    }
+}
 
 impl ServerCertVerifier for GeminiCertVerifier {
     fn verify_server_cert(
         &self,
-        _end_entity: &CertificateDer<'_>,
+        end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
-        _server_name: &ServerName,
+        server_name: &ServerName,
         _ocsp_response: &[u8],
         _now: UnixTime,
     ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
+        if self.trust_all {
+            return Ok(ServerCertVerified::assertion());
+        }
+        let host = server_name.to_str().to_string();
+        let new = fingerprint(end_entity);
+        let mut known_hosts = self.known_hosts.lock().unwrap();
+        match known_hosts.fingerprints.get(&host) {
+            Some(old) if *old != new => {
+                let old = old.clone();
+                known_hosts.mismatch = Some(CertificateMismatch { host, old, new });
+                Err(rustls::Error::General(
+                    "certificate doesn't match the pinned one".to_string(),
+                ))
+            }
+            Some(_) => Ok(ServerCertVerified::assertion()),
+            None => {
+                known_hosts.fingerprints.insert(host, new);
+                Ok(ServerCertVerified::assertion())
+            }
+        }
     }
 
+    // Signatures have to be checked, otherwise anyone could present a pinned certificate
     fn verify_tls12_signature(
         &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
     ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &Self::provider().signature_verification_algorithms,
+        )
     }
 
     fn verify_tls13_signature(
         &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
     ) -> Result<HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &Self::provider().signature_verification_algorithms,
+        )
     }
 
     fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        vec![
-            rustls::SignatureScheme::RSA_PKCS1_SHA1,
-            rustls::SignatureScheme::ECDSA_SHA1_Legacy,
-            rustls::SignatureScheme::RSA_PKCS1_SHA256,
-            rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
-            rustls::SignatureScheme::RSA_PKCS1_SHA384,
-            rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
-            rustls::SignatureScheme::RSA_PKCS1_SHA512,
-            rustls::SignatureScheme::ECDSA_NISTP521_SHA512,
-            rustls::SignatureScheme::RSA_PSS_SHA256,
-            rustls::SignatureScheme::RSA_PSS_SHA384,
-            rustls::SignatureScheme::RSA_PSS_SHA512,
-            rustls::SignatureScheme::ED25519,
-            rustls::SignatureScheme::ED448,
-        ]
+        Self::provider()
+            .signature_verification_algorithms
+            .supported_schemes()
     }
 }